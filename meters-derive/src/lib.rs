@@ -85,6 +85,43 @@ pub fn insert_values_derive_macro(tokens: TokenStream) -> TokenStream {
     .into()
 }
 
+#[proc_macro_derive(UpdateValues)]
+pub fn update_values_derive_macro(tokens: TokenStream) -> TokenStream {
+    let ast = syn::parse::<syn::DeriveInput>(tokens).unwrap();
+
+    let (struct_name, fields) = match ast.data {
+        syn::Data::Struct(data) => (ast.ident, data.fields),
+        _ => panic!(),
+    };
+
+    let mut fields = fields.into_iter();
+    let id = fields.next().unwrap();
+    let (id_name, id_type) = (id.ident.unwrap(), id.ty);
+
+    let mut names = Vec::<Ident>::new();
+    let mut types = Vec::<Type>::new();
+
+    for field in fields {
+        names.push(field.ident.unwrap());
+        types.push(field.ty);
+    }
+
+    quote::quote! {
+        impl UpdateValues for #struct_name {
+            type Values = (#(#types,)* #id_type,);
+
+            fn get_update_values(&self) -> Self::Values {
+                (#(self.#names.clone(),)* self.#id_name.clone(),)
+            }
+
+            fn get_id(&self) -> #id_type {
+                self.#id_name
+            }
+        }
+    }
+    .into()
+}
+
 #[cfg(test)]
 mod test {
     use crate::generate_table_name;
@@ -114,9 +151,9 @@ pub fn from_row_derive_macro(tokens: TokenStream) -> TokenStream {
 
     quote::quote! {
         impl FromRow for #struct_name {
-            fn from_row(row: &Row) -> Self {
+            fn from_row_at(row: &Row, offset: usize) -> Self {
                 #struct_name {
-                    #(#names: row.get(#indexes).unwrap(),)*
+                    #(#names: row.get(offset + #indexes).unwrap(),)*
                 }
             }
         }
@@ -0,0 +1,335 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{Local, NaiveDate};
+use serde_json::{json, Value};
+use subtle::ConstantTimeEq;
+
+use crate::commands::{CommandError, Commands};
+
+/// Local address of the HTTP API, which is off if it isn't set.
+const API_ADDRESS_VAR: &str = "METERS_API_ADDRESS";
+/// Token the clients send as `Authorization: Bearer <token>`.
+const API_TOKEN_VAR: &str = "METERS_API_TOKEN";
+
+/// Author of the changes made through the API in the audit, which isn't a
+/// Telegram user id.
+pub(crate) const API_USER_ID: i64 = 0;
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Settings of the HTTP API over [`Commands`], for reading the data and
+/// submitting readings.
+#[derive(Debug, PartialEq)]
+pub struct ApiConfig {
+    pub address: SocketAddr,
+    pub token: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ApiConfigError {
+    Address(String),
+    /// The API isn't served without a token.
+    MissingToken,
+}
+
+impl std::fmt::Display for ApiConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiConfigError::Address(address) => write!(f, "Invalid API address {}", address),
+            ApiConfigError::MissingToken => write!(f, "{} isn't set", API_TOKEN_VAR),
+        }
+    }
+}
+
+impl ApiConfig {
+    pub fn new(address: &str, token: Option<String>) -> Result<Self, ApiConfigError> {
+        let address = match address.parse::<SocketAddr>() {
+            Ok(parsed) => parsed,
+            Err(_) => return Err(ApiConfigError::Address(address.to_string())),
+        };
+        match token {
+            Some(token) if !token.is_empty() => Ok(ApiConfig { address, token }),
+            _ => Err(ApiConfigError::MissingToken),
+        }
+    }
+
+    /// Reads the settings from the environment, none meaning no API.
+    pub fn from_env() -> Result<Option<Self>, ApiConfigError> {
+        match std::env::var(API_ADDRESS_VAR) {
+            Ok(address) => Self::new(&address, std::env::var(API_TOKEN_VAR).ok()).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    commands: Arc<Commands>,
+    token: Arc<String>,
+}
+
+/// An error as the response, with the status telling what kind of error it is.
+struct ApiError(StatusCode, String);
+
+impl From<CommandError> for ApiError {
+    fn from(error: CommandError) -> Self {
+        let status = match error {
+            CommandError::MeterNotFound(_)
+            | CommandError::MetricNotFound(_)
+            | CommandError::ReadingNotFound(_) => StatusCode::NOT_FOUND,
+            CommandError::MeterArchived(_) => StatusCode::CONFLICT,
+            CommandError::Database(_) | CommandError::File(_) => {
+                log::error!("API request failed: {}", error);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => StatusCode::BAD_REQUEST,
+        };
+        ApiError(status, error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult = Result<Json<Value>, ApiError>;
+
+async fn authorize<B>(
+    State(state): State<ApiState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let expected = std::format!("Bearer {}", state.token);
+    // compared in constant time, so that the time taken tells nothing about the token
+    match request.headers().get(AUTHORIZATION) {
+        Some(value) if bool::from(value.as_bytes().ct_eq(expected.as_bytes())) => {
+            next.run(request).await
+        }
+        _ => ApiError(StatusCode::UNAUTHORIZED, "Invalid token".to_string()).into_response(),
+    }
+}
+
+/// Non-negative number from the query, `default` if there is none.
+fn query_number(
+    query: &HashMap<String, String>,
+    name: &str,
+    default: usize,
+) -> Result<usize, ApiError> {
+    match query.get(name) {
+        Some(value) => value.parse().map_err(|_| {
+            ApiError(
+                StatusCode::BAD_REQUEST,
+                std::format!("Invalid {}: {}", name, value),
+            )
+        }),
+        None => Ok(default),
+    }
+}
+
+/// Meters in use.
+async fn list_meters(State(state): State<ApiState>) -> ApiResult {
+    let meters = state.commands.list_meters().await?;
+    Ok(Json(Value::Array(
+        meters
+            .iter()
+            .map(|x| json!({ "id": x.id, "name": x.name, "archived": x.archived }))
+            .collect(),
+    )))
+}
+
+/// The meter with its metrics, archived or not.
+async fn get_meter(State(state): State<ApiState>, Path(id): Path<u32>) -> ApiResult {
+    let meter = state.commands.get_meter(id).await?;
+    let metrics = state.commands.list_metrics(id).await?;
+    Ok(Json(json!({
+        "id": meter.id,
+        "name": meter.name,
+        "archived": meter.archived,
+        "metrics": metrics
+            .iter()
+            .map(|x| json!({ "id": x.id, "name": x.name, "rate": x.rate }))
+            .collect::<Vec<Value>>(),
+    })))
+}
+
+/// Values of the metric, newest first, a page at a time.
+async fn list_readings(
+    State(state): State<ApiState>,
+    Path(metric_id): Path<u32>,
+    Query(query): Query<HashMap<String, String>>,
+) -> ApiResult {
+    let offset = query_number(&query, "offset", 0)?;
+    let limit = query_number(&query, "limit", DEFAULT_PAGE_SIZE)?.min(MAX_PAGE_SIZE);
+    state.commands.get_metric(metric_id).await?;
+    let page = state
+        .commands
+        .list_readings_page(metric_id, offset, limit)
+        .await?;
+    Ok(Json(json!({
+        "offset": page.offset,
+        "total": page.total,
+        "items": page
+            .items
+            .iter()
+            .map(|x| json!({
+                "id": x.id,
+                "value": x.value,
+                "added": x.added.format("%Y-%m-%d %H:%M:%S").to_string(),
+            }))
+            .collect::<Vec<Value>>(),
+    })))
+}
+
+/// Submits a value of the metric given like `{"value": 1234}`. A value less
+/// than the last one of a counter with known digits is refused as a probable
+/// mistake, unless it is confirmed as a rollover with `"rollover": true`.
+/// Metrics of archived meters take no new values.
+async fn submit_reading(
+    State(state): State<ApiState>,
+    Path(metric_id): Path<u32>,
+    Json(body): Json<Value>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let value = match body["value"].as_u64().map(u32::try_from) {
+        Some(Ok(value)) => value,
+        _ => {
+            return Err(ApiError(
+                StatusCode::BAD_REQUEST,
+                std::format!("Invalid value: {}", body["value"]),
+            ))
+        }
+    };
+    let rollover = body["rollover"].as_bool().unwrap_or(false);
+    let (alerts, _) = match state
+        .commands
+        .for_user(API_USER_ID)
+        .submit_checked_reading(metric_id, value, rollover, Local::now().naive_local())
+        .await
+    {
+        Ok(result) => result,
+        Err(CommandError::ReadingTooSmall(last)) => {
+            return Err(ApiError(
+                StatusCode::CONFLICT,
+                std::format!(
+                    "The value is less than the last one {}, confirm the rollover",
+                    last
+                ),
+            ))
+        }
+        Err(error) => return Err(error.into()),
+    };
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "value": value,
+            "alerts": alerts
+                .iter()
+                .map(|x| json!({ "daily": x.daily, "average": x.average, "factor": x.factor }))
+                .collect::<Vec<Value>>(),
+        })),
+    ))
+}
+
+/// Statement of the month given like `?month=2023-12`, the current one by default.
+async fn get_bill(
+    State(state): State<ApiState>,
+    Query(query): Query<HashMap<String, String>>,
+) -> ApiResult {
+    let month = match query.get("month") {
+        Some(month) => NaiveDate::parse_from_str(&std::format!("{}-01", month), "%Y-%m-%d")
+            .map_err(|_| {
+                ApiError(
+                    StatusCode::BAD_REQUEST,
+                    std::format!("Invalid month: {}", month),
+                )
+            })?,
+        None => Local::now().date_naive(),
+    };
+    let report = state.commands.monthly_report(month).await?;
+    Ok(Json(json!({
+        "month": report.month.format("%Y-%m").to_string(),
+        "lines": report
+            .lines
+            .iter()
+            .map(|x| json!({
+                "meter": x.meter,
+                "metric": x.metric,
+                "start": x.start,
+                "end": x.end,
+                "consumption": x.consumption,
+                "rate": x.rate,
+                "cost": x.cost,
+            }))
+            .collect::<Vec<Value>>(),
+        "total": report.total,
+    })))
+}
+
+pub fn router(commands: Arc<Commands>, token: String) -> Router {
+    let state = ApiState {
+        commands,
+        token: Arc::new(token),
+    };
+    Router::new()
+        .route("/meters", get(list_meters))
+        .route("/meters/:id", get(get_meter))
+        .route(
+            "/metrics/:id/readings",
+            get(list_readings).post(submit_reading),
+        )
+        .route("/bill", get(get_bill))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+/// Serves the API in the background, until the bot stops.
+pub fn spawn_api(config: ApiConfig, commands: Arc<Commands>) {
+    tokio::spawn(async move {
+        log::info!("Serving the API on {}", config.address);
+        let server = axum::Server::bind(&config.address)
+            .serve(router(commands, config.token).into_make_service());
+        if let Err(error) = server.await {
+            log::error!("The API stopped: {}", error);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ApiConfig, ApiConfigError};
+
+    const TOKEN: &str = "token";
+
+    #[test]
+    fn test_api_config() {
+        assert_eq!(
+            ApiConfig::new("127.0.0.1:8080", Some(TOKEN.to_string()))
+                .unwrap()
+                .address
+                .port(),
+            8080
+        );
+        assert_eq!(
+            ApiConfig::new("127.0.0.1:8080", None),
+            Err(ApiConfigError::MissingToken)
+        );
+        assert_eq!(
+            ApiConfig::new("127.0.0.1:8080", Some("".to_string())),
+            Err(ApiConfigError::MissingToken)
+        );
+        assert_eq!(
+            ApiConfig::new("localhost", Some(TOKEN.to_string())),
+            Err(ApiConfigError::Address("localhost".to_string()))
+        );
+    }
+}
@@ -1,28 +1,111 @@
+mod alerts;
+mod backup;
+mod browse;
+mod capacity;
+mod chart;
+mod edit;
+mod export;
+mod history;
+mod import;
+mod language;
 mod meters;
+mod readings;
+mod reminders;
+mod replacement;
+mod report;
+mod table;
+mod undo;
+mod webhook;
 
 use crate::{
-    bot::meters::manage_meters_button, database::create::create_tables_if_do_not_exist, lang::LANG,
+    api::{spawn_api, ApiConfig},
+    bot::meters::start_manage_meters,
+    commands::{self, CommandError, ImportRow},
+    database::structs::Metric,
+};
+use crate::{
+    bot::alerts::receive_alert_factor,
+    bot::backup::{backup_command, receive_backup_file, restore_command},
+    bot::browse::browse_button,
+    bot::capacity::receive_digits,
+    bot::chart::{chart_button, is_chart_action},
+    bot::edit::receive_corrected_reading,
+    bot::export::export_command,
+    bot::history::{history_button, history_command, is_history_action},
+    bot::import::{import_button, import_command, receive_import_file},
+    bot::language::{language_button, start_choose_language},
+    bot::meters::{add_meter_command, manage_meters_button, meters_command},
+    bot::readings::{
+        confirm_rollover_button, latest_readings_button, parse_reading, reading_command,
+        receive_reading, start_latest_readings, submit_readings_button,
+        submit_readings_meter_button, EnteredValue, ACTION_SUBMIT_READINGS,
+    },
+    bot::reminders::{reminder_command, spawn_reminders},
+    bot::replacement::receive_replacement_value,
+    bot::report::{bill_command, report_command},
+    bot::undo::{is_undo_action, undo_button},
+    bot::webhook::WebhookConfig,
+    database::create::create_tables_if_do_not_exist,
+    lang::{Locale, LANGS},
 };
-use crate::{bot::meters::start_manage_meters, commands};
 use rust_i18n::t;
 use std::sync::Arc;
 use teloxide::{
-    dispatching::dialogue::{self, InMemStorage},
+    dispatching::{
+        dialogue::{self, InMemStorage},
+        UpdateHandler,
+    },
+    error_handlers::LoggingErrorHandler,
     handler,
     prelude::*,
-    types::{InlineKeyboardButton, InlineKeyboardMarkup},
-    utils::command::BotCommands,
+    types::{BotCommand, BotCommandScope, InlineKeyboardButton, InlineKeyboardMarkup, Me, User},
+    update_listeners::webhooks,
+    utils::command::{BotCommands, ParseError},
 };
 
-const ALLOWED_CHAT_ID1: ChatId = ChatId(67647522);
-const ALLOWED_CHAT_ID2: ChatId = ChatId(62416549);
+const OWNER_ID: UserId = UserId(67647522);
+
+const DATABASE_PATH_VAR: &str = "METERS_DATABASE";
+/// Directory to keep copies of the photos of counters in, besides Telegram.
+const PHOTOS_PATH_VAR: &str = "METERS_PHOTOS";
 
 const ACTION_MANAGE_METERS: &str = "manage_meters";
+const ACTION_LATEST_READINGS: &str = "latest_readings";
+const ACTION_CHOOSE_LANGUAGE: &str = "choose_language";
 
 pub type SharedCommands = Arc<commands::Commands>;
 
-fn is_allowed_chat(chat_id: ChatId) -> bool {
-    chat_id == ALLOWED_CHAT_ID1
+/// Commands listed by `/help` and in the "/" menu of Telegram, with the keys of
+/// their localized descriptions there.
+const HELP: [(&str, &str, &str); 13] = [
+    ("start", "message.help-start", "message.command-start"),
+    ("help", "message.help-help", "message.command-help"),
+    ("meters", "message.help-meters", "message.command-meters"),
+    (
+        "addmeter",
+        "message.help-addmeter",
+        "message.command-addmeter",
+    ),
+    ("reading", "message.help-reading", "message.command-reading"),
+    ("bill", "message.help-bill", "message.command-bill"),
+    ("report", "message.help-report", "message.command-report"),
+    (
+        "reminder",
+        "message.help-reminder",
+        "message.command-reminder",
+    ),
+    ("export", "message.help-export", "message.command-export"),
+    ("import", "message.help-import", "message.command-import"),
+    ("history", "message.help-history", "message.command-history"),
+    ("backup", "message.help-backup", "message.command-backup"),
+    ("restore", "message.help-restore", "message.command-restore"),
+];
+/// Commands only offered in private chats, as they send or take the whole
+/// database.
+const PRIVATE_COMMANDS: [&str; 2] = ["backup", "restore"];
+
+fn is_owner(user: Option<&User>) -> bool {
+    user.is_some_and(|x| x.id == OWNER_ID)
 }
 
 type MyDialogue = Dialogue<State, InMemStorage<State>>;
@@ -33,6 +116,37 @@ pub enum State {
     #[default]
     Start,
     ManageMeters,
+    LatestReadings,
+    Browse,
+    SubmitReadingsChooseMeter,
+    SubmitReadings {
+        metrics: Vec<Metric>,
+        values: Vec<EnteredValue>,
+    },
+    ChooseLanguage,
+    ImportFile,
+    ImportReadings {
+        rows: Vec<ImportRow>,
+    },
+    RestoreBackup,
+    SetAlertFactor {
+        metric_id: u32,
+    },
+    ConfirmRollover {
+        metrics: Vec<Metric>,
+        values: Vec<EnteredValue>,
+        value: EnteredValue,
+    },
+    SetDigits {
+        metric_id: u32,
+    },
+    ReplaceMeter {
+        metrics: Vec<Metric>,
+        values: Vec<u32>,
+    },
+    EditReading {
+        value_id: u32,
+    },
 }
 
 #[derive(BotCommands, Clone)]
@@ -43,38 +157,305 @@ pub enum State {
 enum Command {
     #[command(description = "Start working with the system")]
     Start,
+    #[command(description = "Show the commands")]
+    Help,
+    #[command(description = "List the meters and their metrics")]
+    Meters,
+    #[command(description = "Add a meter: /addmeter <name>")]
+    AddMeter(String),
+    #[command(
+        parse_with = parse_reading,
+        description = "Submit a reading: /reading <meter> <metric> <value>"
+    )]
+    Reading { names: Vec<String>, value: u32 },
+    #[command(description = "Show what to pay for a month: /bill [YYYY-MM]")]
+    Bill(String),
+    #[command(
+        description = "Show or set the monthly readings reminder: /reminder [off | <first day> <last day> [hour]]"
+    )]
+    Reminder(String),
+    #[command(
+        description = "Export readings as CSV: /export [<from YYYY-MM-DD> [<to YYYY-MM-DD>]]"
+    )]
+    Export(String),
+    #[command(description = "Import readings from a CSV file")]
+    Import,
+    #[command(description = "Show the monthly statement: /report [YYYY-MM]")]
+    Report(String),
+    #[command(description = "Show who changed what and when")]
+    History,
+    #[command(description = "Get a backup of the whole database (owner only)")]
+    Backup,
+    #[command(description = "Replace the whole database with a backup (owner only)")]
+    Restore,
 }
 
 pub async fn start() {
     pretty_env_logger::init();
     log::info!("Starting the bot...");
 
-    rust_i18n::set_locale(LANG);
-    let commands = Arc::new(commands::Commands::in_memory().await.unwrap());
+    // the data is lost on restart unless there is a file to keep it in
+    let commands = match std::env::var(DATABASE_PATH_VAR) {
+        Ok(path) => commands::Commands::open(path.into()).await,
+        Err(_) => commands::Commands::in_memory().await,
+    };
+    let commands = Arc::new(commands.unwrap());
     create_tables_if_do_not_exist(commands.get_connection()).await;
 
+    let webhook = match WebhookConfig::from_env() {
+        Ok(webhook) => webhook,
+        Err(error) => {
+            log::error!("Wrong webhook settings: {}", error);
+            return;
+        }
+    };
+    let api = match ApiConfig::from_env() {
+        Ok(api) => api,
+        Err(error) => {
+            log::error!("Wrong API settings: {}", error);
+            return;
+        }
+    };
+
     let bot = Bot::from_env();
+    register_commands(&bot).await;
+    spawn_reminders(bot.clone(), commands.clone());
+    if let Some(api) = api {
+        spawn_api(api, commands.clone());
+    }
+
+    let mut dispatcher = Dispatcher::builder(bot.clone(), schema())
+        .dependencies(dptree::deps![InMemStorage::<State>::new(), commands])
+        .enable_ctrlc_handler()
+        .build();
 
-    Dispatcher::builder(
-        bot,
-        dialogue::enter::<Update, InMemStorage<State>, State, _>()
-            .branch(
-                Update::filter_message().branch(
+    match webhook {
+        Some(webhook) => {
+            log::info!(
+                "Receiving updates at {} on {}",
+                webhook.url,
+                webhook.address
+            );
+            let listener = match webhooks::axum(bot, webhook.options()).await {
+                Ok(listener) => listener,
+                Err(error) => {
+                    log::error!("Couldn't set up the webhook: {}", error);
+                    return;
+                }
+            };
+            dispatcher
+                .dispatch_with_listener(
+                    listener,
+                    LoggingErrorHandler::with_custom_text("An error from the webhook"),
+                )
+                .await;
+        }
+        None => dispatcher.dispatch().await,
+    }
+}
+
+/// Routes the updates to their handlers by command, dialogue state and
+/// button.
+fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+    dialogue::enter::<Update, InMemStorage<State>, State, _>()
+        .map_async(user_locale)
+        .map(user_commands)
+        .branch(
+            Update::filter_message()
+                .branch(
                     teloxide::filter_command::<Command, _>()
-                        .branch(handler![Command::Start].endpoint(start_command)),
+                        .branch(handler![Command::Start].endpoint(start_command))
+                        .branch(handler![Command::Help].endpoint(help_command))
+                        .branch(handler![Command::Meters].endpoint(meters_command))
+                        .branch(handler![Command::AddMeter(name)].endpoint(add_meter_command))
+                        .branch(
+                            handler![Command::Reading { names, value }].endpoint(reading_command),
+                        )
+                        .branch(handler![Command::Bill(args)].endpoint(bill_command))
+                        .branch(handler![Command::Reminder(args)].endpoint(reminder_command))
+                        .branch(handler![Command::Export(args)].endpoint(export_command))
+                        .branch(handler![Command::Import].endpoint(import_command))
+                        .branch(handler![Command::Report(args)].endpoint(report_command))
+                        .branch(handler![Command::History].endpoint(history_command))
+                        .branch(handler![Command::Backup].endpoint(backup_command))
+                        .branch(handler![Command::Restore].endpoint(restore_command)),
+                )
+                .branch(dptree::filter_map(invalid_command).endpoint(invalid_command_reply))
+                .branch(
+                    handler![State::RestoreBackup]
+                        .chain(Message::filter_document())
+                        .endpoint(receive_backup_file),
+                )
+                .branch(
+                    handler![State::ImportFile]
+                        .chain(Message::filter_document())
+                        .endpoint(receive_import_file),
+                )
+                .branch(
+                    handler![State::SubmitReadings { metrics, values }].endpoint(receive_reading),
+                )
+                .branch(
+                    handler![State::SetAlertFactor { metric_id }].endpoint(receive_alert_factor),
+                )
+                .branch(handler![State::SetDigits { metric_id }].endpoint(receive_digits))
+                .branch(
+                    handler![State::ReplaceMeter { metrics, values }]
+                        .endpoint(receive_replacement_value),
+                )
+                .branch(
+                    handler![State::EditReading { value_id }].endpoint(receive_corrected_reading),
                 ),
-            )
-            .branch(
-                Update::filter_callback_query()
-                    .branch(handler![State::Start].endpoint(start_button))
-                    .branch(handler![State::ManageMeters].endpoint(manage_meters_button)),
-            ),
-    )
-    .dependencies(dptree::deps![InMemStorage::<State>::new(), commands])
-    .enable_ctrlc_handler()
-    .build()
-    .dispatch()
-    .await;
+        )
+        .branch(
+            Update::filter_callback_query()
+                .branch(
+                    dptree::filter(|q: CallbackQuery| {
+                        q.data.as_deref() == Some(ACTION_SUBMIT_READINGS)
+                    })
+                    .endpoint(submit_readings_button),
+                )
+                .branch(
+                    dptree::filter(|q: CallbackQuery| {
+                        q.data.as_deref().is_some_and(is_chart_action)
+                    })
+                    .endpoint(chart_button),
+                )
+                .branch(
+                    dptree::filter(|q: CallbackQuery| {
+                        q.data.as_deref().is_some_and(is_history_action)
+                    })
+                    .endpoint(history_button),
+                )
+                .branch(
+                    dptree::filter(|q: CallbackQuery| {
+                        q.data.as_deref().is_some_and(is_undo_action)
+                    })
+                    .endpoint(undo_button),
+                )
+                .branch(handler![State::Start].endpoint(start_button))
+                .branch(handler![State::ManageMeters].endpoint(manage_meters_button))
+                .branch(handler![State::LatestReadings].endpoint(latest_readings_button))
+                .branch(handler![State::Browse].endpoint(browse_button))
+                .branch(
+                    handler![State::SubmitReadingsChooseMeter]
+                        .endpoint(submit_readings_meter_button),
+                )
+                .branch(handler![State::ChooseLanguage].endpoint(language_button))
+                .branch(handler![State::ImportReadings { rows }].endpoint(import_button))
+                .branch(
+                    handler![State::ConfirmRollover {
+                        metrics,
+                        values,
+                        value
+                    }]
+                    .endpoint(confirm_rollover_button),
+                ),
+        )
+}
+
+/// Localized message saying that `action` failed because of `error`, where
+/// `action` is a key like `error.list-meters`.
+fn error_message(action: &str, error: &CommandError, locale: &Locale) -> String {
+    log::error!("{} failed: {}", action, error);
+    t!(action, locale = &locale, reason = error.localize(locale))
+}
+
+/// Commands of the "/" menu of Telegram in the language of `locale`.
+fn menu_commands(private: bool, locale: &Locale) -> Vec<BotCommand> {
+    HELP.iter()
+        .filter(|(name, _, _)| private || !PRIVATE_COMMANDS.contains(name))
+        .map(|(name, _, key)| BotCommand::new(*name, t!(key, locale = &locale)))
+        .collect()
+}
+
+/// Fills the "/" menu of Telegram in every supported language, English being
+/// the default for the rest.
+async fn register_commands(bot: &Bot) {
+    for (scope, private) in [
+        (BotCommandScope::AllPrivateChats, true),
+        (BotCommandScope::AllGroupChats, false),
+    ] {
+        for language in std::iter::once(None).chain(LANGS.into_iter().map(Some)) {
+            let locale = Locale::new(language.unwrap_or(""));
+            let mut request = bot
+                .set_my_commands(menu_commands(private, &locale))
+                .scope(scope.clone());
+            if let Some(language) = language {
+                request = request.language_code(language);
+            }
+            if let Err(error) = request.await {
+                log::error!(
+                    "Couldn't register the commands for {:?} in {:?}: {}",
+                    scope,
+                    language,
+                    error
+                );
+            }
+        }
+    }
+}
+
+/// Localized explanation of how to call the command, like `addmeter`.
+fn command_usage(command: &str, locale: &Locale) -> String {
+    match HELP.iter().find(|(name, _, _)| *name == command) {
+        Some((_, key, _)) => t!(
+            "message.command-usage",
+            locale = &locale,
+            usage = t!(key, locale = &locale)
+        ),
+        None => t!("message.unknown-command", locale = &locale),
+    }
+}
+
+/// Name of a known command whose arguments couldn't be parsed.
+fn invalid_command(msg: Message, me: Me) -> Option<String> {
+    match Command::parse(msg.text()?, me.username()) {
+        Err(
+            ParseError::TooFewArguments { .. }
+            | ParseError::TooManyArguments { .. }
+            | ParseError::IncorrectFormat(_),
+        ) => {
+            let command = msg.text()?.split_whitespace().next()?;
+            let command = command.trim_start_matches('/').split('@').next()?;
+            Some(command.to_lowercase())
+        }
+        _ => None,
+    }
+}
+
+async fn invalid_command_reply(
+    bot: Bot,
+    msg: Message,
+    locale: Locale,
+    command: String,
+) -> HandlerResult {
+    bot.send_message(msg.chat.id, command_usage(&command, &locale))
+        .await?;
+    Ok(())
+}
+
+/// Commands recording the changes as made by the user of the update.
+fn user_commands(update: Update, commands: SharedCommands) -> SharedCommands {
+    match update.user() {
+        Some(user) => Arc::new(commands.for_user(user.id.0 as i64)),
+        None => commands,
+    }
+}
+
+/// Language chosen by the user, or the one of their Telegram client.
+async fn user_locale(update: Update, commands: SharedCommands) -> Locale {
+    let user = match update.user() {
+        Some(user) => user,
+        None => return Locale::default(),
+    };
+    match commands.get_user_language(user.id.0 as i64).await {
+        Ok(Some(language)) => Locale::new(&language),
+        Ok(None) => Locale::new(user.language_code.as_deref().unwrap_or("")),
+        Err(error) => {
+            log::error!("Couldn't get the language of user {}: {}", user.id, error);
+            Locale::default()
+        }
+    }
 }
 
 async fn start_command(
@@ -82,15 +463,46 @@ async fn start_command(
     dialogue: MyDialogue,
     _commands: SharedCommands,
     msg: Message,
+    locale: Locale,
+) -> HandlerResult {
+    start_menu(bot, dialogue, msg.chat.id, &locale).await
+}
+
+async fn help_command(bot: Bot, msg: Message, locale: Locale) -> HandlerResult {
+    let mut lines = vec![t!("message.help-title", locale = &locale)];
+    for (_, key, _) in HELP {
+        lines.push(t!(key, locale = &locale));
+    }
+    bot.send_message(msg.chat.id, lines.join("\n")).await?;
+    Ok(())
+}
+
+pub async fn start_menu(
+    bot: Bot,
+    dialogue: MyDialogue,
+    chat_id: ChatId,
+    locale: &Locale,
 ) -> HandlerResult {
     dialogue.update(State::Start).await?;
-    let keyboard =
-        InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
-            t!("button.manage-meters"),
+    let keyboard = InlineKeyboardMarkup::default()
+        .append_row(vec![InlineKeyboardButton::callback(
+            t!("button.manage-meters", locale = &locale),
             ACTION_MANAGE_METERS,
+        )])
+        .append_row(vec![InlineKeyboardButton::callback(
+            t!("button.submit-readings", locale = &locale),
+            ACTION_SUBMIT_READINGS,
+        )])
+        .append_row(vec![InlineKeyboardButton::callback(
+            t!("button.latest-readings", locale = &locale),
+            ACTION_LATEST_READINGS,
+        )])
+        .append_row(vec![InlineKeyboardButton::callback(
+            t!("button.choose-language", locale = &locale),
+            ACTION_CHOOSE_LANGUAGE,
         )]);
 
-    bot.send_message(msg.chat.id, t!("message.start"))
+    bot.send_message(chat_id, t!("message.start", locale = &locale))
         .reply_markup(keyboard)
         .await?;
     Ok(())
@@ -99,8 +511,9 @@ async fn start_command(
 async fn start_button(
     bot: Bot,
     my_dialogue: MyDialogue,
-    _commands: SharedCommands,
+    commands: SharedCommands,
     q: CallbackQuery,
+    locale: Locale,
 ) -> HandlerResult {
     if let Some(msg) = &q.message {
         bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
@@ -108,9 +521,65 @@ async fn start_button(
         if let Some(data) = &q.data {
             log::debug!("Start button pressed: {}", data);
             if data == ACTION_MANAGE_METERS {
-                start_manage_meters(bot, my_dialogue, msg.chat.id).await?;
+                start_manage_meters(bot, my_dialogue, msg.chat.id, &locale).await?;
+            } else if data == ACTION_LATEST_READINGS {
+                start_latest_readings(bot, my_dialogue, commands, msg.chat.id, &locale).await?;
+            } else if data == ACTION_CHOOSE_LANGUAGE {
+                start_choose_language(bot, my_dialogue, msg.chat.id, &locale).await?;
             }
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{menu_commands, Command, HELP};
+    use crate::lang::{Locale, LANGS};
+    use teloxide::utils::command::BotCommands;
+
+    #[test]
+    fn test_help_lists_all_commands() {
+        let commands = Command::bot_commands()
+            .into_iter()
+            .map(|x| x.command.trim_start_matches('/').to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(commands.len(), HELP.len());
+        for (name, _, _) in HELP {
+            assert!(commands.contains(&name.to_string()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert!(matches!(
+            Command::parse("/reading water cold 123", "bot"),
+            Ok(Command::Reading { names, value: 123 }) if names == ["water", "cold"]
+        ));
+        assert!(matches!(
+            Command::parse("/reading \"country house\" water 123", "bot"),
+            Ok(Command::Reading { names, value: 123 }) if names == ["country house", "water"]
+        ));
+        assert!(Command::parse("/reading water cold", "bot").is_err());
+        assert!(Command::parse("/reading water cold x", "bot").is_err());
+        assert!(matches!(
+            Command::parse("/addmeter country house", "bot"),
+            Ok(Command::AddMeter(name)) if name == "country house"
+        ));
+    }
+
+    #[test]
+    fn test_menu_commands() {
+        for language in LANGS {
+            let locale = Locale::new(language);
+            let private = menu_commands(true, &locale);
+            assert_eq!(private.len(), HELP.len());
+            for command in &private {
+                assert!((1..=256).contains(&command.description.chars().count()));
+            }
+            let group = menu_commands(false, &locale);
+            assert!(group.iter().all(|x| x.command != "backup"));
+            assert!(group.iter().any(|x| x.command == "reading"));
+        }
+    }
+}
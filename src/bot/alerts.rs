@@ -0,0 +1,68 @@
+use teloxide::{prelude::*, requests::Requester};
+
+use super::{
+    error_message, start_menu, undo::send_undoable, HandlerResult, MyDialogue, SharedCommands,
+    State,
+};
+use crate::lang::Locale;
+use rust_i18n::t;
+
+const ALERTS_OFF: &str = "off";
+
+/// Asks for the factor of the abnormal consumption alerts of the metric.
+pub async fn start_set_alert_factor(
+    bot: Bot,
+    dialogue: MyDialogue,
+    chat_id: ChatId,
+    metric_id: u32,
+    locale: &Locale,
+) -> HandlerResult {
+    dialogue.update(State::SetAlertFactor { metric_id }).await?;
+    bot.send_message(chat_id, t!("message.enter-alert-factor", locale = &locale))
+        .await?;
+    Ok(())
+}
+
+pub async fn receive_alert_factor(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    metric_id: u32,
+) -> HandlerResult {
+    let text = msg.text().unwrap_or("").trim().to_lowercase();
+    let factor = match text.as_str() {
+        ALERTS_OFF => None,
+        // both decimal separators are common
+        factor => match factor.replace(',', ".").parse::<f64>() {
+            Ok(factor) => Some(factor),
+            Err(_) => {
+                bot.send_message(
+                    msg.chat.id,
+                    t!("message.enter-alert-factor", locale = &locale),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    match commands.set_alert_factor(metric_id, factor).await {
+        Ok(action_id) => {
+            let reply = match factor {
+                Some(factor) => t!("message.alert-factor", locale = &locale, factor = factor),
+                None => t!("message.alert-off", locale = &locale),
+            };
+            send_undoable(&bot, msg.chat.id, reply, action_id, &locale).await?;
+        }
+        Err(error) => {
+            bot.send_message(
+                msg.chat.id,
+                error_message("error.set-alert-factor", &error, &locale),
+            )
+            .await?;
+        }
+    };
+    start_menu(bot, dialogue, msg.chat.id, &locale).await
+}
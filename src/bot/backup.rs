@@ -0,0 +1,137 @@
+use chrono::Local;
+use teloxide::{
+    net::Download,
+    prelude::*,
+    requests::Requester,
+    types::{Document, InputFile},
+};
+
+use super::{
+    error_message, is_owner, start_menu, HandlerResult, MyDialogue, SharedCommands, State,
+};
+use crate::lang::Locale;
+use rust_i18n::t;
+
+/// Key of the message refusing `msg`, unless it comes from the owner in a
+/// private chat, so that the data doesn't reach the other members of a group.
+fn refusal(msg: &Message) -> Option<&'static str> {
+    if !is_owner(msg.from()) {
+        Some("message.owner-only")
+    } else if !msg.chat.is_private() {
+        Some("message.private-only")
+    } else {
+        None
+    }
+}
+
+/// `/backup` sends a snapshot of the whole database to the owner.
+pub async fn backup_command(
+    bot: Bot,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+) -> HandlerResult {
+    if let Some(key) = refusal(&msg) {
+        bot.send_message(msg.chat.id, t!(key, locale = &locale))
+            .await?;
+        return Ok(());
+    }
+
+    match commands.backup().await {
+        Ok(backup) => {
+            let file_name = std::format!("meters_{}.sqlite", Local::now().format("%Y-%m-%d"));
+            bot.send_document(msg.chat.id, InputFile::memory(backup).file_name(file_name))
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(msg.chat.id, error_message("error.backup", &error, &locale))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// `/restore` waits for the owner to send a file made by `/backup`.
+pub async fn restore_command(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    locale: Locale,
+) -> HandlerResult {
+    if let Some(key) = refusal(&msg) {
+        bot.send_message(msg.chat.id, t!(key, locale = &locale))
+            .await?;
+        return Ok(());
+    }
+
+    dialogue.update(State::RestoreBackup).await?;
+    bot.send_message(msg.chat.id, t!("message.send-backup", locale = &locale))
+        .await?;
+    Ok(())
+}
+
+pub async fn receive_backup_file(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    document: Document,
+) -> HandlerResult {
+    if let Some(key) = refusal(&msg) {
+        bot.send_message(msg.chat.id, t!(key, locale = &locale))
+            .await?;
+        return Ok(());
+    }
+    log::info!("Restoring backup {:?}", document.file_name);
+
+    let file = bot.get_file(&document.file.id).await?;
+    let mut backup = Vec::new();
+    bot.download_file(&file.path, &mut backup).await?;
+
+    match commands.restore(backup).await {
+        Ok(_) => {
+            bot.send_message(msg.chat.id, t!("message.backup-restored", locale = &locale))
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(msg.chat.id, error_message("error.restore", &error, &locale))
+                .await?;
+        }
+    }
+    start_menu(bot, dialogue, msg.chat.id, &locale).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::refusal;
+    use teloxide::types::Message;
+
+    fn message(chat: &str, user_id: u64) -> Message {
+        serde_json::from_str(&std::format!(
+            r#"{{
+                "message_id": 1,
+                "date": 1700000000,
+                "chat": {},
+                "from": {{"id": {}, "is_bot": false, "first_name": "Test"}},
+                "text": "/backup"
+            }}"#,
+            chat,
+            user_id
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_refusal() {
+        let private = r#"{"id": 67647522, "type": "private", "first_name": "Test"}"#;
+        let group = r#"{"id": -100, "type": "group", "title": "Home"}"#;
+        assert_eq!(refusal(&message(private, 67647522)), None);
+        assert_eq!(
+            refusal(&message(group, 67647522)),
+            Some("message.private-only")
+        );
+        assert_eq!(refusal(&message(private, 1)), Some("message.owner-only"));
+        assert_eq!(refusal(&message(group, 1)), Some("message.owner-only"));
+    }
+}
@@ -0,0 +1,766 @@
+use std::fmt::Display;
+
+use teloxide::{
+    prelude::*,
+    requests::Requester,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, InputFile, ParseMode},
+    utils::html,
+};
+
+use super::{
+    alerts::start_set_alert_factor,
+    capacity::start_set_digits,
+    chart::{ChartAction, ChartStyle, DEFAULT_RANGE},
+    edit::start_edit_reading,
+    error_message,
+    meters::start_manage_meters,
+    replacement::start_replace_meter,
+    table::format_table,
+    undo::undo_action_button,
+    HandlerResult, MyDialogue, SharedCommands, State,
+};
+use crate::{
+    commands::{CommandResult, Page},
+    lang::{format_list, plural_key, Locale},
+};
+use rust_i18n::t;
+
+const PAGE_SIZE: usize = 10;
+
+const ACTION_METERS: &str = "meters";
+const ACTION_ARCHIVED_METERS: &str = "archived_meters";
+const ACTION_ARCHIVE_METER: &str = "archive_meter";
+const ACTION_RESTORE_METER: &str = "restore_meter";
+const ACTION_METRICS: &str = "metrics";
+const ACTION_READINGS: &str = "readings";
+const ACTION_ALERT: &str = "alert";
+const ACTION_DIGITS: &str = "digits";
+const ACTION_REPLACE: &str = "replace";
+const ACTION_PHOTO: &str = "photo";
+const ACTION_READING: &str = "reading";
+const ACTION_EDIT_READING: &str = "edit_reading";
+const ACTION_DELETE_READING: &str = "delete_reading";
+const ACTION_CONFIRM_DELETE_READING: &str = "delete_reading_yes";
+const ACTION_DONE: &str = "done";
+
+/// What a browse button shows, encoded into the callback data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BrowseAction {
+    Meters(usize),
+    ArchivedMeters(usize),
+    ArchiveMeter(u32),
+    RestoreMeter(u32),
+    Metrics(u32, usize),
+    Readings(u32, usize),
+    Alert(u32),
+    Digits(u32),
+    Replace(u32),
+    Photo(u32),
+    Reading(u32),
+    EditReading(u32),
+    DeleteReading(u32),
+    ConfirmDeleteReading(u32),
+    Done,
+}
+
+impl BrowseAction {
+    fn parse(data: &str) -> Option<Self> {
+        let parts = data.split(':').collect::<Vec<&str>>();
+        match parts.as_slice() {
+            [ACTION_METERS, offset] => Some(BrowseAction::Meters(offset.parse().ok()?)),
+            [ACTION_ARCHIVED_METERS, offset] => {
+                Some(BrowseAction::ArchivedMeters(offset.parse().ok()?))
+            }
+            [ACTION_ARCHIVE_METER, meter_id] => {
+                Some(BrowseAction::ArchiveMeter(meter_id.parse().ok()?))
+            }
+            [ACTION_RESTORE_METER, meter_id] => {
+                Some(BrowseAction::RestoreMeter(meter_id.parse().ok()?))
+            }
+            [ACTION_METRICS, meter_id, offset] => Some(BrowseAction::Metrics(
+                meter_id.parse().ok()?,
+                offset.parse().ok()?,
+            )),
+            [ACTION_READINGS, metric_id, offset] => Some(BrowseAction::Readings(
+                metric_id.parse().ok()?,
+                offset.parse().ok()?,
+            )),
+            [ACTION_ALERT, metric_id] => Some(BrowseAction::Alert(metric_id.parse().ok()?)),
+            [ACTION_DIGITS, metric_id] => Some(BrowseAction::Digits(metric_id.parse().ok()?)),
+            [ACTION_REPLACE, meter_id] => Some(BrowseAction::Replace(meter_id.parse().ok()?)),
+            [ACTION_PHOTO, value_id] => Some(BrowseAction::Photo(value_id.parse().ok()?)),
+            [ACTION_READING, value_id] => Some(BrowseAction::Reading(value_id.parse().ok()?)),
+            [ACTION_EDIT_READING, value_id] => {
+                Some(BrowseAction::EditReading(value_id.parse().ok()?))
+            }
+            [ACTION_DELETE_READING, value_id] => {
+                Some(BrowseAction::DeleteReading(value_id.parse().ok()?))
+            }
+            [ACTION_CONFIRM_DELETE_READING, value_id] => {
+                Some(BrowseAction::ConfirmDeleteReading(value_id.parse().ok()?))
+            }
+            [ACTION_DONE] => Some(BrowseAction::Done),
+            _ => None,
+        }
+    }
+
+    fn with_offset(&self, offset: usize) -> Self {
+        match *self {
+            BrowseAction::Meters(_) => BrowseAction::Meters(offset),
+            BrowseAction::ArchivedMeters(_) => BrowseAction::ArchivedMeters(offset),
+            BrowseAction::ArchiveMeter(meter_id) => BrowseAction::ArchiveMeter(meter_id),
+            BrowseAction::RestoreMeter(meter_id) => BrowseAction::RestoreMeter(meter_id),
+            BrowseAction::Metrics(meter_id, _) => BrowseAction::Metrics(meter_id, offset),
+            BrowseAction::Readings(metric_id, _) => BrowseAction::Readings(metric_id, offset),
+            BrowseAction::Alert(metric_id) => BrowseAction::Alert(metric_id),
+            BrowseAction::Digits(metric_id) => BrowseAction::Digits(metric_id),
+            BrowseAction::Replace(meter_id) => BrowseAction::Replace(meter_id),
+            BrowseAction::Photo(value_id) => BrowseAction::Photo(value_id),
+            BrowseAction::Reading(value_id) => BrowseAction::Reading(value_id),
+            BrowseAction::EditReading(value_id) => BrowseAction::EditReading(value_id),
+            BrowseAction::DeleteReading(value_id) => BrowseAction::DeleteReading(value_id),
+            BrowseAction::ConfirmDeleteReading(value_id) => {
+                BrowseAction::ConfirmDeleteReading(value_id)
+            }
+            BrowseAction::Done => BrowseAction::Done,
+        }
+    }
+}
+
+impl Display for BrowseAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrowseAction::Meters(offset) => write!(f, "{}:{}", ACTION_METERS, offset),
+            BrowseAction::ArchivedMeters(offset) => {
+                write!(f, "{}:{}", ACTION_ARCHIVED_METERS, offset)
+            }
+            BrowseAction::ArchiveMeter(meter_id) => {
+                write!(f, "{}:{}", ACTION_ARCHIVE_METER, meter_id)
+            }
+            BrowseAction::RestoreMeter(meter_id) => {
+                write!(f, "{}:{}", ACTION_RESTORE_METER, meter_id)
+            }
+            BrowseAction::Metrics(meter_id, offset) => {
+                write!(f, "{}:{}:{}", ACTION_METRICS, meter_id, offset)
+            }
+            BrowseAction::Readings(metric_id, offset) => {
+                write!(f, "{}:{}:{}", ACTION_READINGS, metric_id, offset)
+            }
+            BrowseAction::Alert(metric_id) => write!(f, "{}:{}", ACTION_ALERT, metric_id),
+            BrowseAction::Digits(metric_id) => write!(f, "{}:{}", ACTION_DIGITS, metric_id),
+            BrowseAction::Replace(meter_id) => write!(f, "{}:{}", ACTION_REPLACE, meter_id),
+            BrowseAction::Photo(value_id) => write!(f, "{}:{}", ACTION_PHOTO, value_id),
+            BrowseAction::Reading(value_id) => write!(f, "{}:{}", ACTION_READING, value_id),
+            BrowseAction::EditReading(value_id) => {
+                write!(f, "{}:{}", ACTION_EDIT_READING, value_id)
+            }
+            BrowseAction::DeleteReading(value_id) => {
+                write!(f, "{}:{}", ACTION_DELETE_READING, value_id)
+            }
+            BrowseAction::ConfirmDeleteReading(value_id) => {
+                write!(f, "{}:{}", ACTION_CONFIRM_DELETE_READING, value_id)
+            }
+            BrowseAction::Done => write!(f, "{}", ACTION_DONE),
+        }
+    }
+}
+
+fn button(text: String, action: BrowseAction) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(text, action.to_string())
+}
+
+fn navigation_row<T>(
+    page: &Page<T>,
+    action: BrowseAction,
+    locale: &Locale,
+) -> Vec<InlineKeyboardButton> {
+    let mut row = Vec::new();
+    if page.offset > 0 {
+        row.push(button(
+            t!("button.previous-page", locale = &locale),
+            action.with_offset(page.offset.saturating_sub(PAGE_SIZE)),
+        ));
+    }
+    if page.offset + PAGE_SIZE < page.total {
+        row.push(button(
+            t!("button.next-page", locale = &locale),
+            action.with_offset(page.offset + PAGE_SIZE),
+        ));
+    }
+    row
+}
+
+fn page_footer<T>(page: &Page<T>, locale: &Locale) -> String {
+    let pages = page.total.div_ceil(PAGE_SIZE);
+    match pages {
+        0 | 1 => "".to_string(),
+        _ => std::format!(
+            "\n\n{}",
+            t!(
+                "message.page",
+                locale = &locale,
+                current = page.offset / PAGE_SIZE + 1,
+                total = pages
+            )
+        ),
+    }
+}
+
+type Screen = (String, InlineKeyboardMarkup);
+
+async fn render_meters(
+    commands: &SharedCommands,
+    offset: usize,
+    locale: &Locale,
+) -> CommandResult<Screen> {
+    let page = commands.list_meters_page(offset, PAGE_SIZE).await?;
+    let text = match page.total {
+        0 => t!("message.no-meters", locale = &locale),
+        total => t!(
+            &plural_key("message.found-meters", total, locale),
+            locale = &locale,
+            count = total,
+            meters = format_list(
+                &page
+                    .items
+                    .iter()
+                    .map(|x| html::escape(&x.name))
+                    .collect::<Vec<String>>(),
+                locale
+            )
+        ),
+    };
+
+    let keyboard = InlineKeyboardMarkup::new(
+        page.items
+            .iter()
+            .map(|x| vec![button(x.name.clone(), BrowseAction::Metrics(x.id, 0))]),
+    )
+    .append_row(navigation_row(&page, BrowseAction::Meters(offset), locale))
+    .append_row(vec![button(
+        t!("button.archived-meters", locale = &locale),
+        BrowseAction::ArchivedMeters(0),
+    )]);
+    Ok((text + &page_footer(&page, locale), keyboard))
+}
+
+async fn render_archived_meters(
+    commands: &SharedCommands,
+    offset: usize,
+    locale: &Locale,
+) -> CommandResult<Screen> {
+    let page = commands
+        .list_archived_meters_page(offset, PAGE_SIZE)
+        .await?;
+    let text = match page.total {
+        0 => t!("message.no-archived-meters", locale = &locale),
+        total => t!(
+            &plural_key("message.found-archived-meters", total, locale),
+            locale = &locale,
+            count = total,
+            meters = format_list(
+                &page
+                    .items
+                    .iter()
+                    .map(|x| html::escape(&x.name))
+                    .collect::<Vec<String>>(),
+                locale
+            )
+        ),
+    };
+
+    let keyboard = InlineKeyboardMarkup::new(
+        page.items
+            .iter()
+            .map(|x| vec![button(x.name.clone(), BrowseAction::Metrics(x.id, 0))]),
+    )
+    .append_row(navigation_row(
+        &page,
+        BrowseAction::ArchivedMeters(offset),
+        locale,
+    ))
+    .append_row(vec![button(
+        t!("button.back", locale = &locale),
+        BrowseAction::Meters(0),
+    )]);
+    Ok((text + &page_footer(&page, locale), keyboard))
+}
+
+/// Archives or restores the meter and shows the list it has left, with a
+/// button undoing that.
+async fn set_meter_archived(
+    commands: &SharedCommands,
+    meter_id: u32,
+    archived: bool,
+    locale: &Locale,
+) -> CommandResult<Screen> {
+    let meter = commands.get_meter(meter_id).await?;
+    let (key, action_id, (text, keyboard)) = match archived {
+        true => (
+            "message.meter-archived",
+            commands.archive_meter(meter_id).await?,
+            render_meters(commands, 0, locale).await?,
+        ),
+        false => (
+            "message.meter-restored",
+            commands.restore_meter(meter_id).await?,
+            render_archived_meters(commands, 0, locale).await?,
+        ),
+    };
+    let keyboard = match undo_action_button(action_id, locale) {
+        Some(button) => keyboard.append_row(vec![button]),
+        None => keyboard,
+    };
+    Ok((
+        std::format!(
+            "{}\n\n{}",
+            t!(key, locale = &locale, meter = html::escape(&meter.name)),
+            text
+        ),
+        keyboard,
+    ))
+}
+
+async fn render_metrics(
+    commands: &SharedCommands,
+    meter_id: u32,
+    offset: usize,
+    locale: &Locale,
+) -> CommandResult<Screen> {
+    let meter = commands.get_meter(meter_id).await?;
+    let page = commands
+        .list_metrics_page(meter_id, offset, PAGE_SIZE)
+        .await?;
+
+    let mut text = t!(
+        &plural_key("message.meter-metrics", page.total, locale),
+        locale = &locale,
+        meter = html::escape(&meter.name),
+        count = page.total
+    );
+    for metric in &page.items {
+        text += &std::format!(
+            "\n• {} ({})",
+            html::escape(&metric.name),
+            t!("message.rate", locale = &locale, rate = metric.rate)
+        );
+    }
+
+    let keyboard = InlineKeyboardMarkup::new(
+        page.items
+            .iter()
+            .map(|x| vec![button(x.name.clone(), BrowseAction::Readings(x.id, 0))]),
+    )
+    .append_row(navigation_row(
+        &page,
+        BrowseAction::Metrics(meter_id, offset),
+        locale,
+    ))
+    .append_row(vec![
+        InlineKeyboardButton::callback(
+            t!("button.chart", locale = &locale),
+            ChartAction {
+                meter_id,
+                range: DEFAULT_RANGE,
+                style: ChartStyle::Bars,
+            }
+            .to_string(),
+        ),
+        button(
+            t!("button.replace-meter", locale = &locale),
+            BrowseAction::Replace(meter_id),
+        ),
+    ])
+    .append_row(vec![match meter.archived {
+        true => button(
+            t!("button.restore-meter", locale = &locale),
+            BrowseAction::RestoreMeter(meter_id),
+        ),
+        false => button(
+            t!("button.archive-meter", locale = &locale),
+            BrowseAction::ArchiveMeter(meter_id),
+        ),
+    }])
+    .append_row(vec![button(
+        t!("button.back", locale = &locale),
+        match meter.archived {
+            true => BrowseAction::ArchivedMeters(0),
+            false => BrowseAction::Meters(0),
+        },
+    )]);
+    Ok((text + &page_footer(&page, locale), keyboard))
+}
+
+async fn render_readings(
+    commands: &SharedCommands,
+    metric_id: u32,
+    offset: usize,
+    locale: &Locale,
+) -> CommandResult<Screen> {
+    let metric = commands.get_metric(metric_id).await?;
+    let page = commands
+        .list_readings_page(metric_id, offset, PAGE_SIZE)
+        .await?;
+
+    let mut text = t!(
+        &plural_key("message.metric-readings", page.total, locale),
+        locale = &locale,
+        metric = html::escape(&metric.name),
+        count = page.total
+    );
+    if !page.items.is_empty() {
+        let mut rows = vec![vec![
+            t!("table.date", locale = &locale),
+            t!("table.value", locale = &locale),
+        ]];
+        for value in &page.items {
+            rows.push(vec![
+                value.added.format("%Y-%m-%d %H:%M").to_string(),
+                value.value.to_string(),
+            ]);
+        }
+        text += &std::format!("\n<pre>{}</pre>", format_table(&rows));
+    }
+    text += "\n";
+    text += &match commands.get_alert_factor(metric_id).await? {
+        Some(factor) => t!("message.alert-factor", locale = &locale, factor = factor),
+        None => t!("message.alert-off", locale = &locale),
+    };
+    if let Some(digits) = commands.get_digits(metric_id).await? {
+        text += "\n";
+        text += &t!(
+            "message.counter-digits",
+            locale = &locale,
+            digits = digits,
+            max = "9".repeat(digits as usize)
+        );
+    }
+
+    if let Some(replacement) = commands.list_replacements(metric_id).await?.first() {
+        text += "\n";
+        text += &t!(
+            "message.last-replacement",
+            locale = &locale,
+            date = replacement.replaced.format("%Y-%m-%d"),
+            old = replacement.old_value,
+            new = replacement.new_value
+        );
+    }
+
+    // a button for every reading on the page to correct or delete it
+    let photos = commands.list_reading_photos(metric_id).await?;
+    let reading_buttons = page
+        .items
+        .iter()
+        .map(|x| {
+            let mut text = std::format!("{}: {}", x.added.format("%Y-%m-%d"), x.value);
+            if photos.iter().any(|photo| photo.metric_value_id == x.id) {
+                text += " 📷";
+            }
+            button(text, BrowseAction::Reading(x.id))
+        })
+        .collect::<Vec<InlineKeyboardButton>>();
+
+    let keyboard = InlineKeyboardMarkup::new(reading_buttons.chunks(2).map(|x| x.to_vec()))
+        .append_row(navigation_row(
+            &page,
+            BrowseAction::Readings(metric_id, offset),
+            locale,
+        ))
+        .append_row(vec![
+            button(
+                t!("button.alert-settings", locale = &locale),
+                BrowseAction::Alert(metric_id),
+            ),
+            button(
+                t!("button.counter-digits", locale = &locale),
+                BrowseAction::Digits(metric_id),
+            ),
+        ])
+        .append_row(vec![button(
+            t!("button.back", locale = &locale),
+            BrowseAction::Metrics(metric.meter_id, 0),
+        )]);
+    Ok((text + &page_footer(&page, locale), keyboard))
+}
+
+async fn render_reading(
+    commands: &SharedCommands,
+    value_id: u32,
+    locale: &Locale,
+) -> CommandResult<Screen> {
+    let reading = commands.get_reading(value_id).await?;
+    let metric = commands.get_metric(reading.metric_id).await?;
+    let text = t!(
+        "message.reading",
+        locale = &locale,
+        metric = html::escape(&metric.name),
+        date = reading.added.format("%Y-%m-%d %H:%M"),
+        value = reading.value
+    );
+
+    let mut keyboard = InlineKeyboardMarkup::default().append_row(vec![
+        button(
+            t!("button.edit-reading", locale = &locale),
+            BrowseAction::EditReading(value_id),
+        ),
+        button(
+            t!("button.delete-reading", locale = &locale),
+            BrowseAction::DeleteReading(value_id),
+        ),
+    ]);
+    if commands.get_reading_photo(value_id).await?.is_some() {
+        keyboard = keyboard.append_row(vec![button(
+            t!("button.reading-photo", locale = &locale),
+            BrowseAction::Photo(value_id),
+        )]);
+    }
+    Ok((
+        text,
+        keyboard.append_row(vec![button(
+            t!("button.back", locale = &locale),
+            BrowseAction::Readings(reading.metric_id, 0),
+        )]),
+    ))
+}
+
+async fn render_delete_reading(
+    commands: &SharedCommands,
+    value_id: u32,
+    locale: &Locale,
+) -> CommandResult<Screen> {
+    let reading = commands.get_reading(value_id).await?;
+    let metric = commands.get_metric(reading.metric_id).await?;
+    let text = t!(
+        "message.confirm-delete-reading",
+        locale = &locale,
+        metric = html::escape(&metric.name),
+        date = reading.added.format("%Y-%m-%d %H:%M"),
+        value = reading.value
+    );
+    let keyboard = InlineKeyboardMarkup::default().append_row(vec![
+        button(
+            t!("button.yes", locale = &locale),
+            BrowseAction::ConfirmDeleteReading(value_id),
+        ),
+        button(
+            t!("button.no", locale = &locale),
+            BrowseAction::Reading(value_id),
+        ),
+    ]);
+    Ok((text, keyboard))
+}
+
+/// Deletes the reading and shows the rest of the readings of its metric.
+async fn delete_reading(
+    commands: &SharedCommands,
+    value_id: u32,
+    locale: &Locale,
+) -> CommandResult<Screen> {
+    let reading = commands.get_reading(value_id).await?;
+    let action_id = commands.delete_reading(value_id).await?;
+    let (text, keyboard) = render_readings(commands, reading.metric_id, 0, locale).await?;
+    let keyboard = match undo_action_button(action_id, locale) {
+        Some(button) => keyboard.append_row(vec![button]),
+        None => keyboard,
+    };
+    Ok((
+        std::format!(
+            "{}\n\n{}",
+            t!("message.reading-deleted", locale = &locale),
+            text
+        ),
+        keyboard,
+    ))
+}
+
+async fn render(
+    commands: &SharedCommands,
+    action: BrowseAction,
+    locale: &Locale,
+) -> CommandResult<Screen> {
+    let (text, keyboard) = match action {
+        BrowseAction::Meters(offset) => render_meters(commands, offset, locale).await?,
+        BrowseAction::ArchivedMeters(offset) => {
+            render_archived_meters(commands, offset, locale).await?
+        }
+        BrowseAction::ArchiveMeter(meter_id) => {
+            set_meter_archived(commands, meter_id, true, locale).await?
+        }
+        BrowseAction::RestoreMeter(meter_id) => {
+            set_meter_archived(commands, meter_id, false, locale).await?
+        }
+        BrowseAction::Metrics(meter_id, offset) => {
+            render_metrics(commands, meter_id, offset, locale).await?
+        }
+        BrowseAction::Readings(metric_id, offset) => {
+            render_readings(commands, metric_id, offset, locale).await?
+        }
+        BrowseAction::Reading(value_id) => render_reading(commands, value_id, locale).await?,
+        BrowseAction::DeleteReading(value_id) => {
+            render_delete_reading(commands, value_id, locale).await?
+        }
+        BrowseAction::ConfirmDeleteReading(value_id) => {
+            delete_reading(commands, value_id, locale).await?
+        }
+        BrowseAction::Alert(_)
+        | BrowseAction::Digits(_)
+        | BrowseAction::Replace(_)
+        | BrowseAction::Photo(_)
+        | BrowseAction::EditReading(_)
+        | BrowseAction::Done => ("".to_string(), InlineKeyboardMarkup::default()),
+    };
+    Ok((
+        text,
+        keyboard.append_row(vec![button(
+            t!("button.done", locale = &locale),
+            BrowseAction::Done,
+        )]),
+    ))
+}
+
+/// Sends the photo of the counter submitted with the value, from Telegram or
+/// from the local copy if Telegram doesn't have it anymore.
+async fn send_reading_photo(
+    bot: &Bot,
+    commands: &SharedCommands,
+    chat_id: ChatId,
+    value_id: u32,
+    locale: &Locale,
+) -> HandlerResult {
+    let photo = match commands.get_reading_photo(value_id).await {
+        Ok(Some(photo)) => photo,
+        Ok(None) => {
+            bot.send_message(chat_id, t!("message.no-reading-photo", locale = &locale))
+                .await?;
+            return Ok(());
+        }
+        Err(error) => {
+            bot.send_message(chat_id, error_message("error.browse", &error, locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match (
+        bot.send_photo(chat_id, InputFile::file_id(&photo.file_id))
+            .await,
+        photo.path,
+    ) {
+        (Ok(_), _) => {}
+        (Err(error), Some(path)) => {
+            log::warn!("Couldn't send photo {}: {}", photo.file_id, error);
+            bot.send_photo(chat_id, InputFile::file(path)).await?;
+        }
+        (Err(error), None) => return Err(error.into()),
+    }
+    Ok(())
+}
+
+/// Sends the first page of meters as a new message, further navigation edits it.
+pub async fn start_browse(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    chat_id: ChatId,
+    locale: &Locale,
+) -> HandlerResult {
+    match render(&commands, BrowseAction::Meters(0), locale).await {
+        Ok((text, keyboard)) => {
+            dialogue.update(State::Browse).await?;
+            bot.send_message(chat_id, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(chat_id, error_message("error.list-meters", &error, locale))
+                .await?;
+            start_manage_meters(bot, dialogue, chat_id, locale).await?;
+        }
+    };
+    Ok(())
+}
+
+pub async fn browse_button(
+    bot: Bot,
+    my_dialogue: MyDialogue,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+) -> HandlerResult {
+    if let (Some(msg), Some(data)) = (&q.message, &q.data) {
+        log::debug!("Browse button pressed: {}", data);
+
+        match BrowseAction::parse(data) {
+            Some(BrowseAction::Done) | None => {
+                bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+                start_manage_meters(bot, my_dialogue, msg.chat.id, &locale).await?;
+            }
+            Some(BrowseAction::Alert(metric_id)) => {
+                bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+                start_set_alert_factor(bot, my_dialogue, msg.chat.id, metric_id, &locale).await?;
+            }
+            Some(BrowseAction::Digits(metric_id)) => {
+                bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+                start_set_digits(bot, my_dialogue, msg.chat.id, metric_id, &locale).await?;
+            }
+            Some(BrowseAction::Photo(value_id)) => {
+                send_reading_photo(&bot, &commands, msg.chat.id, value_id, &locale).await?;
+            }
+            Some(BrowseAction::EditReading(value_id)) => {
+                bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+                start_edit_reading(bot, my_dialogue, commands, msg.chat.id, value_id, &locale)
+                    .await?;
+            }
+            Some(BrowseAction::Replace(meter_id)) => {
+                bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+                start_replace_meter(bot, my_dialogue, commands, msg.chat.id, meter_id, &locale)
+                    .await?;
+            }
+            Some(action) => match render(&commands, action, &locale).await {
+                Ok((text, keyboard)) => {
+                    bot.edit_message_text(msg.chat.id, msg.id, text)
+                        .parse_mode(ParseMode::Html)
+                        .reply_markup(keyboard)
+                        .await?;
+                }
+                Err(error) => {
+                    bot.send_message(msg.chat.id, error_message("error.browse", &error, &locale))
+                        .await?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::BrowseAction;
+
+    #[test]
+    fn test_browse_action_round_trip() {
+        for action in [
+            BrowseAction::Meters(20),
+            BrowseAction::ArchivedMeters(10),
+            BrowseAction::ArchiveMeter(3),
+            BrowseAction::RestoreMeter(3),
+            BrowseAction::Metrics(3, 10),
+            BrowseAction::Readings(7, 0),
+            BrowseAction::Alert(7),
+            BrowseAction::Digits(7),
+            BrowseAction::Replace(3),
+            BrowseAction::Photo(12),
+            BrowseAction::Reading(12),
+            BrowseAction::EditReading(12),
+            BrowseAction::DeleteReading(12),
+            BrowseAction::ConfirmDeleteReading(12),
+            BrowseAction::Done,
+        ] {
+            assert_eq!(BrowseAction::parse(&action.to_string()), Some(action));
+        }
+        assert_eq!(BrowseAction::parse("meters:x"), None);
+        assert_eq!(BrowseAction::parse("unknown"), None);
+    }
+}
@@ -0,0 +1,75 @@
+use teloxide::{prelude::*, requests::Requester};
+
+use super::{
+    error_message, start_menu, undo::send_undoable, HandlerResult, MyDialogue, SharedCommands,
+    State,
+};
+use crate::{commands::MAX_DIGITS, lang::Locale};
+use rust_i18n::t;
+
+const DIGITS_UNKNOWN: &str = "off";
+
+/// Asks how many digits the counter of the metric has.
+pub async fn start_set_digits(
+    bot: Bot,
+    dialogue: MyDialogue,
+    chat_id: ChatId,
+    metric_id: u32,
+    locale: &Locale,
+) -> HandlerResult {
+    dialogue.update(State::SetDigits { metric_id }).await?;
+    bot.send_message(
+        chat_id,
+        t!("message.enter-digits", locale = &locale, max = MAX_DIGITS),
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn receive_digits(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    metric_id: u32,
+) -> HandlerResult {
+    let text = msg.text().unwrap_or("").trim().to_lowercase();
+    let digits = match text.as_str() {
+        DIGITS_UNKNOWN => None,
+        digits => match digits.parse::<u32>() {
+            Ok(digits) => Some(digits),
+            Err(_) => {
+                bot.send_message(
+                    msg.chat.id,
+                    t!("message.enter-digits", locale = &locale, max = MAX_DIGITS),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    match commands.set_digits(metric_id, digits).await {
+        Ok(action_id) => {
+            let reply = match digits {
+                Some(digits) => t!(
+                    "message.counter-digits",
+                    locale = &locale,
+                    digits = digits,
+                    max = "9".repeat(digits as usize)
+                ),
+                None => t!("message.counter-digits-unknown", locale = &locale),
+            };
+            send_undoable(&bot, msg.chat.id, reply, action_id, &locale).await?;
+        }
+        Err(error) => {
+            bot.send_message(
+                msg.chat.id,
+                error_message("error.set-digits", &error, &locale),
+            )
+            .await?;
+        }
+    };
+    start_menu(bot, dialogue, msg.chat.id, &locale).await
+}
@@ -0,0 +1,399 @@
+use std::{fmt::Display, io::Cursor, sync::Once};
+
+use chrono::{Local, Months, NaiveDate};
+use plotters::{
+    prelude::*,
+    style::{register_font, FontStyle},
+};
+use teloxide::{
+    prelude::*,
+    requests::Requester,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, InputFile, InputMedia, InputMediaPhoto},
+};
+
+use super::{error_message, HandlerResult, SharedCommands};
+use crate::{commands::month_start, lang::Locale};
+use rust_i18n::t;
+
+const ACTION_CHART: &str = "chart";
+const RANGE_ALL: &str = "all";
+const STYLE_BARS: &str = "bars";
+const STYLE_LINES: &str = "lines";
+pub const DEFAULT_RANGE: ChartRange = ChartRange::Months(6);
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 500;
+// the font is embedded so that charts don't depend on the fonts of the system
+const FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+const FONT_FAMILY: &str = "sans-serif";
+static REGISTER_FONT: Once = Once::new();
+
+type ChartResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// How many last months a chart shows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChartRange {
+    Months(u32),
+    All,
+}
+
+/// Consumption of every month as bars, or its running total as lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChartStyle {
+    Bars,
+    Lines,
+}
+
+/// A chart button, encoded into the callback data like `chart:3:6:bars`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChartAction {
+    pub meter_id: u32,
+    pub range: ChartRange,
+    pub style: ChartStyle,
+}
+
+impl ChartAction {
+    pub fn parse(data: &str) -> Option<Self> {
+        let parts = data.split(':').collect::<Vec<&str>>();
+        let (meter_id, range, style) = match parts.as_slice() {
+            [ACTION_CHART, meter_id, range, STYLE_BARS] => (meter_id, range, ChartStyle::Bars),
+            [ACTION_CHART, meter_id, range, STYLE_LINES] => (meter_id, range, ChartStyle::Lines),
+            _ => return None,
+        };
+        Some(ChartAction {
+            meter_id: meter_id.parse().ok()?,
+            range: match *range {
+                RANGE_ALL => ChartRange::All,
+                months => ChartRange::Months(months.parse().ok().filter(|x| *x > 0)?),
+            },
+            style,
+        })
+    }
+}
+
+impl Display for ChartAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let style = match self.style {
+            ChartStyle::Bars => STYLE_BARS,
+            ChartStyle::Lines => STYLE_LINES,
+        };
+        match self.range {
+            ChartRange::Months(months) => {
+                write!(f, "{}:{}:{}:{}", ACTION_CHART, self.meter_id, months, style)
+            }
+            ChartRange::All => write!(
+                f,
+                "{}:{}:{}:{}",
+                ACTION_CHART, self.meter_id, RANGE_ALL, style
+            ),
+        }
+    }
+}
+
+/// Whether the callback data is of a chart button, which works in any state.
+pub fn is_chart_action(data: &str) -> bool {
+    ChartAction::parse(data).is_some()
+}
+
+/// Draws the monthly consumption of every named series as grouped bars, or
+/// its running total since the first month as lines.
+fn render_chart(
+    title: &str,
+    series: &[(String, Vec<(NaiveDate, u32)>)],
+    style: ChartStyle,
+) -> ChartResult<Vec<u8>> {
+    REGISTER_FONT.call_once(|| {
+        if register_font(FONT_FAMILY, FontStyle::Normal, FONT).is_err() {
+            log::error!("Couldn't register the chart font");
+        }
+    });
+
+    // every month between the first and the last one, even without values
+    let mut months = Vec::new();
+    let first = series
+        .iter()
+        .filter_map(|(_, x)| x.first())
+        .map(|x| x.0)
+        .min();
+    let last = series
+        .iter()
+        .filter_map(|(_, x)| x.last())
+        .map(|x| x.0)
+        .max();
+    if let (Some(mut month), Some(last)) = (first, last) {
+        while month <= last {
+            months.push(month);
+            month = match month.checked_add_months(Months::new(1)) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+    // values by the position of their month, totals carried over the months
+    // without consumption
+    let series = series
+        .iter()
+        .map(|(name, values)| {
+            let mut points = values
+                .iter()
+                .filter_map(|(month, value)| {
+                    Some((months.iter().position(|x| x == month)?, *value))
+                })
+                .collect::<Vec<(usize, u32)>>();
+            if style == ChartStyle::Lines {
+                let mut total = 0;
+                let first = points.first().map_or(months.len(), |x| x.0);
+                points = (first..months.len())
+                    .map(|position| {
+                        total += points
+                            .iter()
+                            .filter(|x| x.0 == position)
+                            .map(|x| x.1)
+                            .sum::<u32>();
+                        (position, total)
+                    })
+                    .collect();
+            }
+            (name, points)
+        })
+        .collect::<Vec<(&String, Vec<(usize, u32)>)>>();
+    let max = series
+        .iter()
+        .flat_map(|(_, x)| x.iter().map(|x| x.1))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let bar_width = 0.8 / series.len().max(1) as f64;
+
+    let mut buffer = vec![0; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, (FONT_FAMILY, 24))
+            .margin(15)
+            .x_label_area_size(40)
+            .y_label_area_size(60)
+            .build_cartesian_2d(-0.5..months.len() as f64 - 0.5, 0..max + max / 10)?;
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_labels(months.len())
+            .x_label_formatter(&|x| match months.get(x.round().max(0.0) as usize) {
+                Some(month) if (x - x.round()).abs() < 0.01 => month.format("%m.%Y").to_string(),
+                _ => "".to_string(),
+            })
+            .label_style((FONT_FAMILY, 14))
+            .draw()?;
+
+        for (index, (name, points)) in series.iter().enumerate() {
+            let color = Palette99::pick(index).to_rgba();
+            match style {
+                ChartStyle::Bars => chart
+                    .draw_series(points.iter().map(|(position, value)| {
+                        let left = *position as f64 - 0.4 + bar_width * index as f64;
+                        Rectangle::new([(left, 0), (left + bar_width, *value)], color.filled())
+                    }))?
+                    .label(name.as_str())
+                    .legend(move |(x, y)| {
+                        Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
+                    }),
+                ChartStyle::Lines => chart
+                    .draw_series(
+                        LineSeries::new(
+                            points
+                                .iter()
+                                .map(|(position, value)| (*position as f64, *value)),
+                            color.stroke_width(2),
+                        )
+                        .point_size(3),
+                    )?
+                    .label(name.as_str())
+                    .legend(move |(x, y)| {
+                        PathElement::new([(x, y), (x + 10, y)], color.stroke_width(2))
+                    }),
+            };
+        }
+        chart
+            .configure_series_labels()
+            .label_font((FONT_FAMILY, 14))
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        root.present()?;
+    }
+
+    let image = image::RgbImage::from_raw(WIDTH, HEIGHT, buffer).ok_or("Wrong image size")?;
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+    Ok(png)
+}
+
+/// Buttons showing the chart for another range, or in the other style.
+fn chart_keyboard(action: ChartAction, locale: &Locale) -> InlineKeyboardMarkup {
+    let button = |text: String, range, style| {
+        InlineKeyboardButton::callback(
+            text,
+            ChartAction {
+                range,
+                style,
+                ..action
+            }
+            .to_string(),
+        )
+    };
+    InlineKeyboardMarkup::default()
+        .append_row(vec![
+            button(
+                t!("button.chart-half-year", locale = &locale),
+                ChartRange::Months(6),
+                action.style,
+            ),
+            button(
+                t!("button.chart-year", locale = &locale),
+                ChartRange::Months(12),
+                action.style,
+            ),
+            button(
+                t!("button.chart-all", locale = &locale),
+                ChartRange::All,
+                action.style,
+            ),
+        ])
+        .append_row(vec![match action.style {
+            ChartStyle::Bars => button(
+                t!("button.chart-lines", locale = &locale),
+                action.range,
+                ChartStyle::Lines,
+            ),
+            ChartStyle::Lines => button(
+                t!("button.chart-bars", locale = &locale),
+                action.range,
+                ChartStyle::Bars,
+            ),
+        }])
+}
+
+/// Sends the chart of the meter, or replaces the chart in the message with it.
+pub async fn chart_button(
+    bot: Bot,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+) -> HandlerResult {
+    let (msg, action) = match (&q.message, q.data.as_deref().and_then(ChartAction::parse)) {
+        (Some(msg), Some(action)) => (msg, action),
+        _ => return Ok(()),
+    };
+    log::debug!("Chart button pressed: {}", action);
+
+    let since = match action.range {
+        ChartRange::Months(months) => {
+            month_start(Local::now().date_naive()).checked_sub_months(Months::new(months - 1))
+        }
+        ChartRange::All => None,
+    };
+    let (meter, consumption) = match (
+        commands.get_meter(action.meter_id).await,
+        commands.monthly_consumption(action.meter_id, since).await,
+    ) {
+        (Ok(meter), Ok(consumption)) => (meter, consumption),
+        (Err(error), _) | (_, Err(error)) => {
+            bot.send_message(msg.chat.id, error_message("error.chart", &error, &locale))
+                .await?;
+            return Ok(());
+        }
+    };
+    if consumption.iter().all(|(_, x)| x.is_empty()) {
+        bot.send_message(
+            msg.chat.id,
+            t!(
+                "message.no-consumption",
+                locale = &locale,
+                meter = meter.name
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let title = match action.style {
+        ChartStyle::Bars => t!("message.chart-title", locale = &locale, meter = meter.name),
+        ChartStyle::Lines => t!(
+            "message.chart-running-title",
+            locale = &locale,
+            meter = meter.name
+        ),
+    };
+    let series = consumption
+        .into_iter()
+        .map(|(metric, months)| (metric.name, months))
+        .collect::<Vec<(String, Vec<(NaiveDate, u32)>)>>();
+    let style = action.style;
+    let png = match tokio::task::spawn_blocking(move || render_chart(&title, &series, style)).await
+    {
+        Ok(Ok(png)) => png,
+        Ok(Err(error)) => return Err(error),
+        Err(error) => return Err(error.into()),
+    };
+
+    let photo = InputFile::memory(png).file_name("chart.png");
+    let keyboard = chart_keyboard(action, &locale);
+    if msg.photo().is_some() {
+        bot.edit_message_media(
+            msg.chat.id,
+            msg.id,
+            InputMedia::Photo(InputMediaPhoto::new(photo)),
+        )
+        .reply_markup(keyboard)
+        .await?;
+    } else {
+        bot.send_photo(msg.chat.id, photo)
+            .reply_markup(keyboard)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_chart, ChartAction, ChartRange, ChartStyle};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_chart_action_round_trip() {
+        for action in [
+            ChartAction {
+                meter_id: 3,
+                range: ChartRange::Months(6),
+                style: ChartStyle::Bars,
+            },
+            ChartAction {
+                meter_id: 1,
+                range: ChartRange::All,
+                style: ChartStyle::Lines,
+            },
+        ] {
+            assert_eq!(ChartAction::parse(&action.to_string()), Some(action));
+        }
+        assert_eq!(ChartAction::parse("chart:3:12"), None);
+        assert_eq!(ChartAction::parse("chart:3:12:pie"), None);
+        assert_eq!(ChartAction::parse("chart:1:0:bars"), None);
+        assert_eq!(ChartAction::parse("chart:x:6:bars"), None);
+        assert_eq!(ChartAction::parse("meters:0"), None);
+    }
+
+    #[test]
+    fn test_render_chart() {
+        let month = |month| NaiveDate::from_ymd_opt(2023, month, 1).unwrap();
+        let series = [
+            ("day".to_string(), vec![(month(9), 10), (month(12), 50)]),
+            ("night".to_string(), vec![(month(10), 5)]),
+        ];
+        for style in [ChartStyle::Bars, ChartStyle::Lines] {
+            let png = render_chart("Электричество", &series, style).unwrap();
+            assert!(png.starts_with(b"\x89PNG"));
+        }
+    }
+}
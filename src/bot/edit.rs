@@ -0,0 +1,82 @@
+use teloxide::{prelude::*, requests::Requester};
+
+use super::{
+    error_message, start_menu, undo::send_undoable, HandlerResult, MyDialogue, SharedCommands,
+    State,
+};
+use crate::{commands::CommandError, lang::Locale};
+use rust_i18n::t;
+
+/// Asks for the correct value of a submitted reading.
+pub async fn start_edit_reading(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    chat_id: ChatId,
+    value_id: u32,
+    locale: &Locale,
+) -> HandlerResult {
+    match commands.get_reading(value_id).await {
+        Ok(reading) => {
+            dialogue.update(State::EditReading { value_id }).await?;
+            bot.send_message(
+                chat_id,
+                t!(
+                    "message.enter-corrected-reading",
+                    locale = &locale,
+                    date = reading.added.format("%Y-%m-%d %H:%M"),
+                    value = reading.value
+                ),
+            )
+            .await?;
+            Ok(())
+        }
+        Err(error) => {
+            bot.send_message(chat_id, error_message("error.edit-reading", &error, locale))
+                .await?;
+            start_menu(bot, dialogue, chat_id, locale).await
+        }
+    }
+}
+
+pub async fn receive_corrected_reading(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    value_id: u32,
+) -> HandlerResult {
+    let value = match msg.text().map(|x| x.trim().parse::<u32>()) {
+        Some(Ok(value)) => value,
+        _ => {
+            bot.send_message(msg.chat.id, t!("message.invalid-reading", locale = &locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match commands.update_reading(value_id, value).await {
+        Ok(action_id) => {
+            let reply = t!("message.reading-corrected", locale = &locale, value = value);
+            send_undoable(&bot, msg.chat.id, reply, action_id, &locale).await?;
+        }
+        // a value out of the series may be entered again
+        Err(error @ (CommandError::ReadingTooSmall(_) | CommandError::ReadingTooLarge(_))) => {
+            bot.send_message(
+                msg.chat.id,
+                error_message("error.edit-reading", &error, &locale),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(error) => {
+            bot.send_message(
+                msg.chat.id,
+                error_message("error.edit-reading", &error, &locale),
+            )
+            .await?;
+        }
+    };
+    start_menu(bot, dialogue, msg.chat.id, &locale).await
+}
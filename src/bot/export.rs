@@ -0,0 +1,63 @@
+use chrono::NaiveDate;
+use teloxide::{prelude::*, requests::Requester, types::InputFile};
+
+use super::{error_message, HandlerResult, SharedCommands};
+use crate::lang::Locale;
+use rust_i18n::t;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// `/export [from [to]]` sends the readings of the date range as a CSV file.
+pub async fn export_command(
+    bot: Bot,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    args: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let dates = args
+        .split_whitespace()
+        .map(|x| NaiveDate::parse_from_str(x, DATE_FORMAT))
+        .collect::<Result<Vec<NaiveDate>, _>>();
+
+    let (from, to) = match dates.as_deref() {
+        Ok([]) => (None, None),
+        Ok([from]) => (Some(*from), None),
+        Ok([from, to]) if from <= to => (Some(*from), Some(*to)),
+        _ => {
+            bot.send_message(chat_id, t!("message.export-usage", locale = &locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match commands.export_readings(from, to).await {
+        // only the header
+        Ok(csv) if csv.lines().count() <= 1 => {
+            bot.send_message(
+                chat_id,
+                t!("message.no-readings-to-export", locale = &locale),
+            )
+            .await?;
+        }
+        Ok(csv) => {
+            let file_name = match (from, to) {
+                (Some(from), Some(to)) => std::format!(
+                    "readings_{}_{}.csv",
+                    from.format(DATE_FORMAT),
+                    to.format(DATE_FORMAT)
+                ),
+                (Some(from), None) => std::format!("readings_{}.csv", from.format(DATE_FORMAT)),
+                _ => "readings.csv".to_string(),
+            };
+            bot.send_document(chat_id, InputFile::memory(csv).file_name(file_name))
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(chat_id, error_message("error.export", &error, &locale))
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use teloxide::{
+    prelude::*,
+    requests::Requester,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode},
+    utils::html,
+};
+
+use super::{error_message, HandlerResult, SharedCommands};
+use crate::{
+    api::API_USER_ID,
+    commands::{CommandResult, Page},
+    database::structs::AuditRecord,
+    lang::Locale,
+};
+use rust_i18n::t;
+
+const ACTION_HISTORY: &str = "history";
+/// The states of the rows are long, so that a page fits into a message.
+const PAGE_SIZE: usize = 5;
+
+/// Offset of the page of changes a history button shows, like `history:10`.
+fn parse_history_action(data: &str) -> Option<usize> {
+    match data.split(':').collect::<Vec<&str>>().as_slice() {
+        [ACTION_HISTORY, offset] => offset.parse().ok(),
+        _ => None,
+    }
+}
+
+fn history_action(offset: usize) -> String {
+    std::format!("{}:{}", ACTION_HISTORY, offset)
+}
+
+/// Whether the callback data is of a history button, which works in any state.
+pub fn is_history_action(data: &str) -> bool {
+    parse_history_action(data).is_some()
+}
+
+/// Renders a state of a row as `column: value` pairs, leaving out the id
+/// which is already in the record.
+fn format_row(row: &str) -> String {
+    serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(row)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(name, _)| name != "id")
+        .map(|(name, value)| match value {
+            serde_json::Value::String(value) => std::format!("{}: {}", name, value),
+            value => std::format!("{}: {}", name, value),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn format_record(record: &AuditRecord, locale: &Locale) -> String {
+    let user = match record.user_id {
+        Some(API_USER_ID) => t!("message.audit-api", locale = &locale),
+        Some(user_id) => t!("message.audit-user", locale = &locale, id = user_id),
+        None => t!("message.audit-bot", locale = &locale),
+    };
+    let mut lines = vec![t!(
+        "message.audit-record",
+        locale = &locale,
+        date = record.changed.format("%Y-%m-%d %H:%M"),
+        user = user,
+        action = &record.action,
+        entity = &record.entity,
+        id = record.entity_id
+    )];
+    if let Some(before) = &record.before {
+        lines.push(t!(
+            "message.audit-before",
+            locale = &locale,
+            value = html::escape(&format_row(before))
+        ));
+    }
+    if let Some(after) = &record.after {
+        lines.push(t!(
+            "message.audit-after",
+            locale = &locale,
+            value = html::escape(&format_row(after))
+        ));
+    }
+    lines.join("\n")
+}
+
+fn format_history(page: &Page<AuditRecord>, locale: &Locale) -> String {
+    if page.items.is_empty() {
+        return t!("message.no-history", locale = &locale);
+    }
+    let mut text = t!("message.history-title", locale = &locale);
+    for record in &page.items {
+        text += "\n\n";
+        text += &format_record(record, locale);
+    }
+    let pages = page.total.div_ceil(PAGE_SIZE);
+    if pages > 1 {
+        text += "\n\n";
+        text += &t!(
+            "message.page",
+            locale = &locale,
+            current = page.offset / PAGE_SIZE + 1,
+            total = pages
+        );
+    }
+    text
+}
+
+fn history_keyboard(page: &Page<AuditRecord>, locale: &Locale) -> InlineKeyboardMarkup {
+    let mut row = Vec::new();
+    if page.offset > 0 {
+        row.push(InlineKeyboardButton::callback(
+            t!("button.previous-page", locale = &locale),
+            history_action(page.offset.saturating_sub(PAGE_SIZE)),
+        ));
+    }
+    if page.offset + PAGE_SIZE < page.total {
+        row.push(InlineKeyboardButton::callback(
+            t!("button.next-page", locale = &locale),
+            history_action(page.offset + PAGE_SIZE),
+        ));
+    }
+    InlineKeyboardMarkup::default().append_row(row)
+}
+
+async fn render_history(
+    commands: &SharedCommands,
+    offset: usize,
+    locale: &Locale,
+) -> CommandResult<(String, InlineKeyboardMarkup)> {
+    let page = commands.list_audit_page(offset, PAGE_SIZE).await?;
+    Ok((
+        format_history(&page, locale),
+        history_keyboard(&page, locale),
+    ))
+}
+
+/// Shows the latest changes of the data, who made them and how.
+pub async fn history_command(
+    bot: Bot,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+) -> HandlerResult {
+    match render_history(&commands, 0, &locale).await {
+        Ok((text, keyboard)) => {
+            bot.send_message(msg.chat.id, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(msg.chat.id, error_message("error.history", &error, &locale))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn history_button(
+    bot: Bot,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+) -> HandlerResult {
+    let (msg, offset) = match (&q.message, q.data.as_deref().and_then(parse_history_action)) {
+        (Some(msg), Some(offset)) => (msg, offset),
+        _ => return Ok(()),
+    };
+    log::debug!("History button pressed: {}", offset);
+
+    match render_history(&commands, offset, &locale).await {
+        Ok((text, keyboard)) => {
+            bot.edit_message_text(msg.chat.id, msg.id, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(msg.chat.id, error_message("error.history", &error, &locale))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_record, format_row, history_action, parse_history_action};
+    use crate::{api::API_USER_ID, database::structs::AuditRecord, lang::Locale};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_history_action_round_trip() {
+        assert_eq!(parse_history_action(&history_action(15)), Some(15));
+        assert_eq!(parse_history_action("history:x"), None);
+        assert_eq!(parse_history_action("chart:1:6"), None);
+    }
+
+    #[test]
+    fn test_format_row() {
+        assert_eq!(
+            format_row(r#"{"id":3,"name":"water","rate":null,"added":"2023-12-01 10:00:00"}"#),
+            "name: water, rate: null, added: 2023-12-01 10:00:00"
+        );
+        assert_eq!(format_row(r#"{"id":3}"#), "");
+    }
+
+    #[test]
+    fn test_format_record_author() {
+        let changed = NaiveDate::from_ymd_opt(2023, 12, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let record =
+            |user_id| AuditRecord::new(user_id, &changed, "meter", 3, "delete", None, None);
+        let locale = Locale::new("en");
+        assert!(format_record(&record(Some(42)), &locale).contains("user 42"));
+        assert!(format_record(&record(Some(API_USER_ID)), &locale).contains("the API"));
+        assert!(format_record(&record(None), &locale).contains("the bot"));
+    }
+}
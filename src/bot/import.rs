@@ -0,0 +1,267 @@
+use teloxide::{
+    net::Download,
+    prelude::*,
+    requests::Requester,
+    types::{Document, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode},
+    utils::html,
+};
+
+use super::{
+    error_message, start_menu, undo::send_undoable, HandlerResult, MyDialogue, SharedCommands,
+    State,
+};
+use crate::{
+    commands::{ImportError, ImportPlan, ImportRow},
+    lang::{format_list, plural_key, Locale},
+};
+use rust_i18n::t;
+
+const ACTION_IMPORT_ALL: &str = "import_all";
+const ACTION_IMPORT_EXISTING: &str = "import_existing";
+const ACTION_CANCEL_IMPORT: &str = "import_cancel";
+
+/// Lines with errors listed in the summary, the rest are only counted.
+const MAX_ERRORS_SHOWN: usize = 10;
+/// Types Telegram clients give to CSV files, besides the extension.
+const CSV_MIME_TYPES: [&str; 3] = ["text/csv", "text/comma-separated-values", "application/csv"];
+
+fn import_error_key(error: ImportError) -> &'static str {
+    match error {
+        ImportError::Header => "error.import-header",
+        ImportError::Format => "error.import-format",
+        ImportError::EmptyName => "error.import-empty-name",
+        ImportError::Value => "error.import-value",
+        ImportError::Rate => "error.import-rate",
+        ImportError::Timestamp => "error.import-timestamp",
+    }
+}
+
+fn import_summary(plan: &ImportPlan, locale: &Locale) -> String {
+    let mut lines = vec![match plan.rows.len() {
+        0 => t!("message.import-nothing", locale = &locale),
+        count => t!(
+            &plural_key("message.import-rows", count, locale),
+            locale = &locale,
+            count = count
+        ),
+    }];
+    if plan.duplicates > 0 {
+        lines.push(t!(
+            &plural_key("message.import-duplicates", plan.duplicates, locale),
+            locale = &locale,
+            count = plan.duplicates
+        ));
+    }
+    if !plan.missing_meters.is_empty() {
+        lines.push(t!(
+            "message.import-missing-meters",
+            locale = &locale,
+            meters = format_list(
+                &plan
+                    .missing_meters
+                    .iter()
+                    .map(|x| html::escape(x))
+                    .collect::<Vec<String>>(),
+                locale
+            )
+        ));
+    }
+    if !plan.missing_metrics.is_empty() {
+        lines.push(t!(
+            "message.import-missing-metrics",
+            locale = &locale,
+            metrics = format_list(
+                &plan
+                    .missing_metrics
+                    .iter()
+                    .map(|(meter, metric)| {
+                        std::format!("{} / {}", html::escape(meter), html::escape(metric))
+                    })
+                    .collect::<Vec<String>>(),
+                locale
+            )
+        ));
+    }
+    if !plan.errors.is_empty() {
+        lines.push(t!(
+            &plural_key("message.import-errors", plan.errors.len(), locale),
+            locale = &locale,
+            count = plan.errors.len()
+        ));
+        for (line, error) in plan.errors.iter().take(MAX_ERRORS_SHOWN) {
+            lines.push(t!(
+                "message.import-error-line",
+                locale = &locale,
+                line = line,
+                reason = t!(import_error_key(*error), locale = &locale)
+            ));
+        }
+        if plan.errors.len() > MAX_ERRORS_SHOWN {
+            lines.push("…".to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+fn is_csv_file(file_name: Option<&str>, mime_type: Option<&str>) -> bool {
+    file_name.is_some_and(|x| x.to_lowercase().ends_with(".csv"))
+        || mime_type.is_some_and(|x| CSV_MIME_TYPES.contains(&x))
+}
+
+/// `/import` waits for a CSV file in the format of `/export`.
+pub async fn import_command(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    locale: Locale,
+) -> HandlerResult {
+    dialogue.update(State::ImportFile).await?;
+    bot.send_message(
+        msg.chat.id,
+        t!("message.send-import-file", locale = &locale),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Checks an uploaded CSV file and asks whether to import it.
+pub async fn receive_import_file(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    document: Document,
+) -> HandlerResult {
+    log::debug!("Import file received: {:?}", document.file_name);
+    if !is_csv_file(
+        document.file_name.as_deref(),
+        document.mime_type.as_ref().map(|x| x.essence_str()),
+    ) {
+        bot.send_message(msg.chat.id, t!("message.import-not-csv", locale = &locale))
+            .await?;
+        return Ok(());
+    }
+
+    let file = bot.get_file(&document.file.id).await?;
+    let mut csv = Vec::new();
+    bot.download_file(&file.path, &mut csv).await?;
+
+    let plan = match commands.plan_import(csv).await {
+        Ok(plan) => plan,
+        Err(error) => {
+            bot.send_message(msg.chat.id, error_message("error.import", &error, &locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let summary = import_summary(&plan, &locale);
+    if plan.rows.is_empty() {
+        bot.send_message(msg.chat.id, summary)
+            .parse_mode(ParseMode::Html)
+            .await?;
+        return Ok(());
+    }
+
+    let keyboard = if plan.missing_meters.is_empty() && plan.missing_metrics.is_empty() {
+        InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+            t!("button.import", locale = &locale),
+            ACTION_IMPORT_EXISTING,
+        )])
+    } else {
+        InlineKeyboardMarkup::default()
+            .append_row(vec![InlineKeyboardButton::callback(
+                t!("button.import-create-missing", locale = &locale),
+                ACTION_IMPORT_ALL,
+            )])
+            .append_row(vec![InlineKeyboardButton::callback(
+                t!("button.import-existing", locale = &locale),
+                ACTION_IMPORT_EXISTING,
+            )])
+    }
+    .append_row(vec![InlineKeyboardButton::callback(
+        t!("button.cancel", locale = &locale),
+        ACTION_CANCEL_IMPORT,
+    )]);
+
+    dialogue
+        .update(State::ImportReadings { rows: plan.rows })
+        .await?;
+    bot.send_message(msg.chat.id, summary)
+        .parse_mode(ParseMode::Html)
+        .reply_markup(keyboard)
+        .await?;
+    Ok(())
+}
+
+pub async fn import_button(
+    bot: Bot,
+    my_dialogue: MyDialogue,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+    rows: Vec<ImportRow>,
+) -> HandlerResult {
+    if let Some(msg) = &q.message {
+        bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+
+        if let Some(data) = &q.data {
+            log::debug!("Import button pressed: {}", data);
+
+            let create_missing = match data.as_str() {
+                ACTION_IMPORT_ALL => Some(true),
+                ACTION_IMPORT_EXISTING => Some(false),
+                _ => None,
+            };
+            match create_missing {
+                Some(create_missing) => {
+                    match commands.import_readings(rows, create_missing).await {
+                        Ok((count, action_id)) => {
+                            let text = t!(
+                                &plural_key("message.readings-imported", count, &locale),
+                                locale = &locale,
+                                count = count
+                            );
+                            send_undoable(&bot, msg.chat.id, text, action_id, &locale).await?;
+                        }
+                        Err(error) => {
+                            bot.send_message(
+                                msg.chat.id,
+                                error_message("error.import", &error, &locale),
+                            )
+                            .await?;
+                        }
+                    }
+                }
+                None => {
+                    bot.send_message(
+                        msg.chat.id,
+                        t!("message.import-cancelled", locale = &locale),
+                    )
+                    .await?;
+                }
+            };
+        }
+        start_menu(bot, my_dialogue, msg.chat.id, &locale).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_csv_file;
+
+    #[test]
+    fn test_is_csv_file() {
+        assert!(is_csv_file(Some("readings.csv"), None));
+        assert!(is_csv_file(
+            Some("READINGS.CSV"),
+            Some("application/octet-stream")
+        ));
+        assert!(is_csv_file(None, Some("text/comma-separated-values")));
+        assert!(!is_csv_file(Some("photo.jpg"), Some("image/jpeg")));
+        assert!(!is_csv_file(Some("backup.sqlite"), None));
+        assert!(!is_csv_file(None, None));
+    }
+}
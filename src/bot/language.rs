@@ -0,0 +1,67 @@
+use teloxide::{
+    prelude::*,
+    requests::Requester,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+use super::{error_message, start_menu, HandlerResult, MyDialogue, SharedCommands, State};
+use crate::lang::{Locale, LANGS};
+use rust_i18n::t;
+
+pub async fn start_choose_language(
+    bot: Bot,
+    dialogue: MyDialogue,
+    chat_id: ChatId,
+    locale: &Locale,
+) -> HandlerResult {
+    dialogue.update(State::ChooseLanguage).await?;
+    // every language is named in itself
+    let keyboard = InlineKeyboardMarkup::new(LANGS.iter().map(|x| {
+        vec![InlineKeyboardButton::callback(
+            t!("language.name", locale = x),
+            x.to_string(),
+        )]
+    }));
+    bot.send_message(chat_id, t!("message.choose-language", locale = &locale))
+        .reply_markup(keyboard)
+        .await?;
+    Ok(())
+}
+
+pub async fn language_button(
+    bot: Bot,
+    my_dialogue: MyDialogue,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+) -> HandlerResult {
+    if let Some(msg) = &q.message {
+        bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+
+        let mut locale = locale;
+        if let Some(data) = &q.data {
+            log::debug!("Language button pressed: {}", data);
+
+            let chosen = Locale::new(data);
+            match commands
+                .set_user_language(q.from.id.0 as i64, &chosen)
+                .await
+            {
+                Ok(_) => {
+                    locale = chosen;
+                    bot.send_message(msg.chat.id, t!("message.language-set", locale = &locale))
+                        .await?;
+                }
+                Err(error) => {
+                    bot.send_message(
+                        msg.chat.id,
+                        error_message("error.set-language", &error, &locale),
+                    )
+                    .await?;
+                }
+            }
+        }
+        start_menu(bot, my_dialogue, msg.chat.id, &locale).await?;
+    }
+    Ok(())
+}
@@ -1,25 +1,45 @@
 use teloxide::{
     prelude::*,
     requests::Requester,
-    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode},
+    utils::html,
 };
 
-use super::{HandlerResult, MyDialogue, SharedCommands, State};
+use super::{
+    browse::start_browse, command_usage, error_message, undo::send_undoable, HandlerResult,
+    MyDialogue, SharedCommands, State,
+};
+use crate::{
+    database::structs::Meter,
+    lang::{format_list, Locale},
+};
 use rust_i18n::t;
 
 const ACTION_LIST_METERS: &str = "list_meter";
-const ACTION_ADD_METER: &str = "add_meter";
-const ACTION_DELETE_METER: &str = "delete_meter";
-const ACTION_EDIT_METER: &str = "edit_meter";
 
-pub async fn start_manage_meters(bot: Bot, dialogue: MyDialogue, chat_id: ChatId) -> HandlerResult {
+/// One button per meter, the callback data being the meter id.
+pub fn meter_selection_keyboard(meters: &[Meter]) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(meters.iter().map(|x| {
+        vec![InlineKeyboardButton::callback(
+            x.name.clone(),
+            x.id.to_string(),
+        )]
+    }))
+}
+
+pub async fn start_manage_meters(
+    bot: Bot,
+    dialogue: MyDialogue,
+    chat_id: ChatId,
+    locale: &Locale,
+) -> HandlerResult {
     dialogue.update(State::ManageMeters).await?;
     let keyboard =
         InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
-            t!("button.list-meters"),
+            t!("button.list-meters", locale = &locale),
             ACTION_LIST_METERS,
         )]);
-    bot.send_message(chat_id, t!("message.managing-meters"))
+    bot.send_message(chat_id, t!("message.managing-meters", locale = &locale))
         .reply_markup(keyboard)
         .await?;
     Ok(())
@@ -30,6 +50,7 @@ pub async fn manage_meters_button(
     my_dialogue: MyDialogue,
     guarded_commands: SharedCommands,
     q: CallbackQuery,
+    locale: Locale,
 ) -> HandlerResult {
     if let Some(msg) = &q.message {
         bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
@@ -38,47 +59,98 @@ pub async fn manage_meters_button(
             log::debug!("Manage meters button pressed: {}", data);
 
             if data == ACTION_LIST_METERS {
-                list_meters(bot, my_dialogue, guarded_commands, msg.chat.id).await?;
+                start_browse(bot, my_dialogue, guarded_commands, msg.chat.id, &locale).await?;
             }
         }
     }
     Ok(())
 }
 
-pub async fn list_meters(
+/// `/meters` lists the meters in use with their metrics.
+pub async fn meters_command(
     bot: Bot,
-    my_dialogue: MyDialogue,
     commands: SharedCommands,
-    chat_id: ChatId,
+    msg: Message,
+    locale: Locale,
 ) -> HandlerResult {
-    match commands.list_meters().await {
-        Ok(found_meters) => {
+    let meters = match commands.list_meters().await {
+        Ok(meters) => meters,
+        Err(error) => {
             bot.send_message(
-                chat_id,
-                t!(
-                    "message.found-meters",
-                    count = found_meters.len(),
-                    ending = match found_meters.len() {
-                        0 => ".".to_string(),
-                        _ => std::format!(
-                            ": {}.",
-                            found_meters
-                                .iter()
-                                .map(|x| x.name.clone())
-                                .collect::<Vec<String>>()
-                                .join(", ")
-                        ),
-                    }
-                ),
+                msg.chat.id,
+                error_message("error.list-meters", &error, &locale),
             )
             .await?;
-        }
-        Err(error) => {
-            bot.send_message(chat_id, std::format!("Couldn't list meters: {}", error))
-                .await?;
+            return Ok(());
         }
     };
+    if meters.is_empty() {
+        bot.send_message(msg.chat.id, t!("message.no-meters", locale = &locale))
+            .await?;
+        return Ok(());
+    }
 
-    start_manage_meters(bot, my_dialogue, chat_id).await?;
+    let mut text = t!("message.meters-title", locale = &locale);
+    for meter in meters {
+        let metrics = match commands.list_metrics(meter.id).await {
+            Ok(metrics) => metrics,
+            Err(error) => {
+                bot.send_message(
+                    msg.chat.id,
+                    error_message("error.list-metrics", &error, &locale),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+        text += &match metrics.is_empty() {
+            true => std::format!("\n• <b>{}</b>", html::escape(&meter.name)),
+            false => std::format!(
+                "\n• <b>{}</b>: {}",
+                html::escape(&meter.name),
+                format_list(
+                    &metrics
+                        .iter()
+                        .map(|x| html::escape(&x.name))
+                        .collect::<Vec<String>>(),
+                    &locale
+                )
+            ),
+        };
+    }
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// `/addmeter <name>` adds a meter without metrics.
+pub async fn add_meter_command(
+    bot: Bot,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    name: String,
+) -> HandlerResult {
+    let name = name.trim();
+    if name.is_empty() {
+        bot.send_message(msg.chat.id, command_usage("addmeter", &locale))
+            .await?;
+        return Ok(());
+    }
+
+    match commands.add_meter(name).await {
+        Ok(action_id) => {
+            let reply = t!("message.meter-added", locale = &locale, meter = name);
+            send_undoable(&bot, msg.chat.id, reply, action_id, &locale).await?;
+        }
+        Err(error) => {
+            bot.send_message(
+                msg.chat.id,
+                error_message("error.add-meter", &error, &locale),
+            )
+            .await?;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,667 @@
+use std::path::PathBuf;
+
+use chrono::Local;
+use teloxide::{
+    net::Download,
+    prelude::*,
+    requests::Requester,
+    types::{FileMeta, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode},
+    utils::command::ParseError,
+    utils::html,
+};
+
+use super::{
+    error_message, meters::meter_selection_keyboard, start_menu, table::format_table,
+    undo::send_undoable, HandlerResult, MyDialogue, SharedCommands, State, PHOTOS_PATH_VAR,
+};
+use crate::{
+    commands::ReadingCheck,
+    database::structs::{Metric, ReadingPhoto},
+    lang::Locale,
+};
+use rust_i18n::t;
+
+pub const ACTION_SUBMIT_READINGS: &str = "submit_readings";
+const ACTION_CONFIRM_ROLLOVER: &str = "rollover_yes";
+const ACTION_REJECT_ROLLOVER: &str = "rollover_no";
+
+/// A value entered for a metric, with the photo of the counter if it was sent.
+#[derive(Clone, Debug)]
+pub struct EnteredValue {
+    pub value: u32,
+    pub photo: Option<FileMeta>,
+}
+
+pub async fn start_latest_readings(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    chat_id: ChatId,
+    locale: &Locale,
+) -> HandlerResult {
+    match commands.list_meters().await {
+        Ok(found_meters) if found_meters.is_empty() => {
+            bot.send_message(chat_id, t!("message.no-meters", locale = &locale))
+                .await?;
+            start_menu(bot, dialogue, chat_id, locale).await?;
+        }
+        Ok(found_meters) => {
+            dialogue.update(State::LatestReadings).await?;
+            bot.send_message(chat_id, t!("message.choose-meter", locale = &locale))
+                .reply_markup(meter_selection_keyboard(&found_meters))
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(chat_id, error_message("error.list-meters", &error, locale))
+                .await?;
+            start_menu(bot, dialogue, chat_id, locale).await?;
+        }
+    };
+    Ok(())
+}
+
+pub async fn latest_readings_button(
+    bot: Bot,
+    my_dialogue: MyDialogue,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+) -> HandlerResult {
+    if let Some(msg) = &q.message {
+        bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+
+        if let Some(data) = &q.data {
+            log::debug!("Latest readings button pressed: {}", data);
+
+            if let Ok(meter_id) = data.parse::<u32>() {
+                show_latest_readings(bot.clone(), commands, msg.chat.id, meter_id, &locale).await?;
+            }
+        }
+        start_menu(bot, my_dialogue, msg.chat.id, &locale).await?;
+    }
+    Ok(())
+}
+
+async fn show_latest_readings(
+    bot: Bot,
+    commands: SharedCommands,
+    chat_id: ChatId,
+    meter_id: u32,
+    locale: &Locale,
+) -> HandlerResult {
+    let meter = match commands.get_meter(meter_id).await {
+        Ok(meter) => meter,
+        Err(error) => {
+            bot.send_message(chat_id, error_message("error.find-meter", &error, locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match commands.latest_readings(meter_id).await {
+        Ok(readings) if readings.is_empty() => {
+            bot.send_message(
+                chat_id,
+                t!(
+                    "message.no-metrics",
+                    locale = &locale,
+                    meter = html::escape(&meter.name)
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .await?;
+        }
+        Ok(readings) => {
+            let mut rows = vec![vec![
+                t!("table.metric", locale = &locale),
+                t!("table.last-value", locale = &locale),
+                t!("table.date", locale = &locale),
+            ]];
+            for (metric, value) in readings {
+                rows.push(match value {
+                    Some(value) => vec![
+                        metric.name,
+                        value.value.to_string(),
+                        value.added.format("%Y-%m-%d").to_string(),
+                    ],
+                    None => vec![metric.name, "-".to_string(), "-".to_string()],
+                });
+            }
+
+            bot.send_message(
+                chat_id,
+                std::format!(
+                    "{}\n<pre>{}</pre>",
+                    t!(
+                        "message.latest-readings",
+                        locale = &locale,
+                        meter = html::escape(&meter.name)
+                    ),
+                    format_table(&rows)
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .await?;
+        }
+        Err(error) => {
+            bot.send_message(
+                chat_id,
+                error_message("error.latest-readings", &error, locale),
+            )
+            .await?;
+        }
+    };
+    Ok(())
+}
+
+pub async fn start_submit_readings(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    chat_id: ChatId,
+    locale: &Locale,
+) -> HandlerResult {
+    match commands.list_meters().await {
+        Ok(found_meters) if found_meters.is_empty() => {
+            bot.send_message(chat_id, t!("message.no-meters", locale = &locale))
+                .await?;
+            start_menu(bot, dialogue, chat_id, locale).await?;
+        }
+        Ok(found_meters) => {
+            dialogue.update(State::SubmitReadingsChooseMeter).await?;
+            bot.send_message(chat_id, t!("message.choose-meter", locale = &locale))
+                .reply_markup(meter_selection_keyboard(&found_meters))
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(chat_id, error_message("error.list-meters", &error, locale))
+                .await?;
+            start_menu(bot, dialogue, chat_id, locale).await?;
+        }
+    };
+    Ok(())
+}
+
+/// Handles the submit readings button wherever it is, e.g. in a reminder.
+pub async fn submit_readings_button(
+    bot: Bot,
+    my_dialogue: MyDialogue,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+) -> HandlerResult {
+    if let Some(msg) = &q.message {
+        bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+        start_submit_readings(bot, my_dialogue, commands, msg.chat.id, &locale).await?;
+    }
+    Ok(())
+}
+
+pub async fn submit_readings_meter_button(
+    bot: Bot,
+    my_dialogue: MyDialogue,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+) -> HandlerResult {
+    if let Some(msg) = &q.message {
+        bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+
+        if let Some(meter_id) = q.data.as_deref().and_then(|x| x.parse::<u32>().ok()) {
+            log::debug!("Submit readings meter button pressed: {}", meter_id);
+
+            match commands.list_metrics(meter_id).await {
+                Ok(metrics) if metrics.is_empty() => {
+                    bot.send_message(
+                        msg.chat.id,
+                        t!("message.no-metrics-to-submit", locale = &locale),
+                    )
+                    .await?;
+                }
+                Ok(metrics) => {
+                    bot.send_message(
+                        msg.chat.id,
+                        t!("message.reading-photo-hint", locale = &locale),
+                    )
+                    .await?;
+                    ask_reading(&bot, msg.chat.id, &metrics[0], &locale).await?;
+                    my_dialogue
+                        .update(State::SubmitReadings {
+                            metrics,
+                            values: vec![],
+                        })
+                        .await?;
+                    return Ok(());
+                }
+                Err(error) => {
+                    bot.send_message(
+                        msg.chat.id,
+                        error_message("error.list-metrics", &error, &locale),
+                    )
+                    .await?;
+                }
+            }
+        }
+        start_menu(bot, my_dialogue, msg.chat.id, &locale).await?;
+    }
+    Ok(())
+}
+
+/// Keeps a copy of the photo in the directory of photos, if there is one,
+/// and returns its path. The photo stays available in Telegram anyway, so a
+/// failure is only logged.
+async fn save_photo(bot: &Bot, photo: &FileMeta) -> Option<String> {
+    let directory = PathBuf::from(std::env::var(PHOTOS_PATH_VAR).ok()?);
+    let path = directory.join(std::format!("{}.jpg", photo.unique_id));
+    let result: HandlerResult = async {
+        tokio::fs::create_dir_all(&directory).await?;
+        let file = bot.get_file(&photo.id).await?;
+        let mut destination = tokio::fs::File::create(&path).await?;
+        bot.download_file(&file.path, &mut destination).await?;
+        Ok(())
+    }
+    .await;
+    match result {
+        Ok(_) => Some(path.to_string_lossy().to_string()),
+        Err(error) => {
+            log::error!("Couldn't save photo {}: {}", photo.unique_id, error);
+            None
+        }
+    }
+}
+
+async fn ask_reading(
+    bot: &Bot,
+    chat_id: ChatId,
+    metric: &Metric,
+    locale: &Locale,
+) -> HandlerResult {
+    bot.send_message(
+        chat_id,
+        t!(
+            "message.enter-reading",
+            locale = &locale,
+            metric = html::escape(&metric.name)
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+pub async fn receive_reading(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    (metrics, values): (Vec<Metric>, Vec<EnteredValue>),
+) -> HandlerResult {
+    // a photo of the counter comes with the value as its caption
+    let photo = msg.photo().and_then(|x| x.last()).map(|x| x.file.clone());
+    let value = match msg
+        .text()
+        .or(msg.caption())
+        .map(|x| x.trim().parse::<u32>())
+    {
+        Some(Ok(value)) => value,
+        _ => {
+            let key = match photo {
+                Some(_) => "message.photo-without-reading",
+                None => "message.invalid-reading",
+            };
+            bot.send_message(msg.chat.id, t!(key, locale = &locale))
+                .await?;
+            return Ok(());
+        }
+    };
+    accept_reading(
+        bot,
+        dialogue,
+        commands,
+        msg.chat.id,
+        &locale,
+        (metrics, values),
+        EnteredValue { value, photo },
+    )
+    .await
+}
+
+/// Takes the value of the next metric unless it looks wrong, asking whether a
+/// value less than the last one is a counter rollover.
+pub async fn accept_reading(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    chat_id: ChatId,
+    locale: &Locale,
+    (metrics, mut values): (Vec<Metric>, Vec<EnteredValue>),
+    value: EnteredValue,
+) -> HandlerResult {
+    let metric = &metrics[values.len()];
+    match commands.check_reading(metric.id, value.value).await {
+        Ok(ReadingCheck::Ok) => {}
+        Ok(ReadingCheck::TooLarge(digits)) => {
+            bot.send_message(
+                chat_id,
+                t!(
+                    "message.reading-too-large",
+                    locale = locale,
+                    digits = digits
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+        Ok(ReadingCheck::Rollover(last)) => {
+            let keyboard = InlineKeyboardMarkup::default().append_row(vec![
+                InlineKeyboardButton::callback(
+                    t!("button.yes", locale = locale),
+                    ACTION_CONFIRM_ROLLOVER,
+                ),
+                InlineKeyboardButton::callback(
+                    t!("button.no", locale = locale),
+                    ACTION_REJECT_ROLLOVER,
+                ),
+            ]);
+            bot.send_message(
+                chat_id,
+                t!(
+                    "message.confirm-rollover",
+                    locale = locale,
+                    metric = html::escape(&metric.name),
+                    last = last,
+                    value = value.value
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .reply_markup(keyboard)
+            .await?;
+            dialogue
+                .update(State::ConfirmRollover {
+                    metrics,
+                    values,
+                    value,
+                })
+                .await?;
+            return Ok(());
+        }
+        Err(error) => {
+            bot.send_message(
+                chat_id,
+                error_message("error.submit-readings", &error, locale),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    values.push(value);
+    next_reading(bot, dialogue, commands, chat_id, locale, metrics, values).await
+}
+
+/// Parses the arguments of `/reading` into the words of the names and the
+/// value, which is the last word. Names with spaces may be quoted, like
+/// `/reading "country house" water 123`.
+pub fn parse_reading(input: String) -> Result<(Vec<String>, u32), ParseError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    for char in input.chars() {
+        match char {
+            '"' | '“' | '”' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            _ if char.is_whitespace() && !quoted => words.extend(word.take()),
+            _ => word.get_or_insert_with(String::new).push(char),
+        }
+    }
+    words.extend(word);
+
+    let found = words.len();
+    let value = match words.pop() {
+        Some(value) if found >= 3 => value,
+        _ => {
+            return Err(ParseError::TooFewArguments {
+                expected: 3,
+                found,
+                message: "A meter, a metric and a value are required".to_string(),
+            })
+        }
+    };
+    match value.parse() {
+        Ok(value) => Ok((words, value)),
+        Err(error) => Err(ParseError::IncorrectFormat(Box::new(error))),
+    }
+}
+
+/// Ways to read the words as a meter name followed by a metric name.
+fn name_splits(names: &[String]) -> Vec<(String, String)> {
+    (1..names.len())
+        .map(|x| (names[..x].join(" "), names[x..].join(" ")))
+        .collect()
+}
+
+/// `/reading <meter> <metric> <value>` takes the value of a single metric, the
+/// names being matched regardless of case. Names of several words are tried
+/// to be split in every way, unless they are quoted.
+pub async fn reading_command(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    (names, value): (Vec<String>, u32),
+) -> HandlerResult {
+    let meters = match commands.list_meters().await {
+        Ok(meters) => meters,
+        Err(error) => {
+            bot.send_message(
+                msg.chat.id,
+                error_message("error.list-meters", &error, &locale),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let splits = name_splits(&names);
+    // the meter found first and the rest of the names if no metric matches
+    let mut unknown_metric = None;
+    for (meter, metric) in &splits {
+        let found = match meters
+            .iter()
+            .find(|x| x.name.to_lowercase() == meter.to_lowercase())
+        {
+            Some(found) => found,
+            None => continue,
+        };
+        let found = match commands.list_metrics(found.id).await {
+            Ok(metrics) => metrics
+                .into_iter()
+                .find(|x| x.name.to_lowercase() == metric.to_lowercase()),
+            Err(error) => {
+                bot.send_message(
+                    msg.chat.id,
+                    error_message("error.list-metrics", &error, &locale),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+        match found {
+            Some(found) => {
+                return accept_reading(
+                    bot,
+                    dialogue,
+                    commands,
+                    msg.chat.id,
+                    &locale,
+                    (vec![found], Vec::new()),
+                    EnteredValue { value, photo: None },
+                )
+                .await
+            }
+            None => {
+                unknown_metric.get_or_insert((meter, metric));
+            }
+        }
+    }
+
+    let text = match unknown_metric {
+        Some((meter, metric)) => t!(
+            "message.unknown-metric",
+            locale = &locale,
+            meter = html::escape(meter),
+            metric = html::escape(metric)
+        ),
+        // the longest name the meter may have
+        None => t!(
+            "message.unknown-meter",
+            locale = &locale,
+            meter = html::escape(&names[..names.len() - 1].join(" "))
+        ),
+    };
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Handles the answer whether a value less than the last one is a rollover or
+/// a mistake, in which case the value is asked again.
+pub async fn confirm_rollover_button(
+    bot: Bot,
+    my_dialogue: MyDialogue,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+    (metrics, mut values, value): (Vec<Metric>, Vec<EnteredValue>, EnteredValue),
+) -> HandlerResult {
+    if let Some(msg) = &q.message {
+        bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+        log::debug!("Confirm rollover button pressed: {:?}", q.data);
+
+        if q.data.as_deref() == Some(ACTION_CONFIRM_ROLLOVER) {
+            values.push(value);
+            next_reading(
+                bot,
+                my_dialogue,
+                commands,
+                msg.chat.id,
+                &locale,
+                metrics,
+                values,
+            )
+            .await?;
+        } else {
+            ask_reading(&bot, msg.chat.id, &metrics[values.len()], &locale).await?;
+            my_dialogue
+                .update(State::SubmitReadings { metrics, values })
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Asks the value of the next metric, or submits all of them if there are no more.
+async fn next_reading(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    chat_id: ChatId,
+    locale: &Locale,
+    metrics: Vec<Metric>,
+    values: Vec<EnteredValue>,
+) -> HandlerResult {
+    if values.len() < metrics.len() {
+        ask_reading(&bot, chat_id, &metrics[values.len()], locale).await?;
+        dialogue
+            .update(State::SubmitReadings { metrics, values })
+            .await?;
+        return Ok(());
+    }
+
+    let mut readings = Vec::new();
+    for (metric, value) in metrics.iter().zip(values) {
+        let photo = match value.photo {
+            Some(photo) => Some(ReadingPhoto::new(&photo.id, save_photo(&bot, &photo).await)),
+            None => None,
+        };
+        readings.push((metric.id, value.value, photo));
+    }
+    match commands
+        .submit_readings_with_photos(readings, Local::now().naive_local())
+        .await
+    {
+        Ok((alerts, action_id)) => {
+            let reply = t!("message.readings-submitted", locale = &locale);
+            send_undoable(&bot, chat_id, reply, action_id, locale).await?;
+            for alert in alerts {
+                bot.send_message(
+                    chat_id,
+                    t!(
+                        "message.consumption-alert",
+                        locale = &locale,
+                        metric = html::escape(&alert.metric.name),
+                        daily = std::format!("{:.1}", alert.daily),
+                        average = std::format!("{:.1}", alert.average),
+                        ratio = std::format!("{:.1}", alert.daily / alert.average)
+                    ),
+                )
+                .parse_mode(ParseMode::Html)
+                .await?;
+            }
+        }
+        Err(error) => {
+            bot.send_message(
+                chat_id,
+                error_message("error.submit-readings", &error, locale),
+            )
+            .await?;
+        }
+    }
+    start_menu(bot, dialogue, chat_id, locale).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{name_splits, parse_reading};
+
+    #[test]
+    fn test_parse_reading() {
+        assert_eq!(
+            parse_reading("water cold 123".to_string()).unwrap(),
+            (vec!["water".to_string(), "cold".to_string()], 123)
+        );
+        assert_eq!(
+            parse_reading("\"country house\"  “hot water” 7".to_string()).unwrap(),
+            (
+                vec!["country house".to_string(), "hot water".to_string()],
+                7
+            )
+        );
+        assert_eq!(
+            parse_reading("country house water 7".to_string())
+                .unwrap()
+                .0
+                .len(),
+            3
+        );
+        assert!(parse_reading("water 123".to_string()).is_err());
+        assert!(parse_reading("\"water cold\" 123".to_string()).is_err());
+        assert!(parse_reading("water cold x".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_name_splits() {
+        let names = ["country", "house", "water"].map(String::from);
+        assert_eq!(
+            name_splits(&names),
+            vec![
+                ("country".to_string(), "house water".to_string()),
+                ("country house".to_string(), "water".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use chrono::Local;
+use teloxide::{
+    prelude::*,
+    requests::Requester,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+use super::{error_message, readings::ACTION_SUBMIT_READINGS, HandlerResult, SharedCommands};
+use crate::lang::{format_list, Locale};
+use rust_i18n::t;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_HOUR: u32 = 10;
+
+/// Periodically sends reminders that are due, until the bot stops.
+pub fn spawn_reminders(bot: Bot, commands: SharedCommands) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            send_due_reminders(&bot, &commands).await;
+        }
+    });
+}
+
+async fn send_due_reminders(bot: &Bot, commands: &SharedCommands) {
+    let now = Local::now().naive_local();
+    let due = match commands.due_reminders(now).await {
+        Ok(due) => due,
+        Err(error) => {
+            log::error!("Couldn't check reminders: {}", error);
+            return;
+        }
+    };
+
+    for (reminder, missing) in due {
+        // reminders set before their language was kept use the one of the
+        // chat's user, which is right for private chats
+        let locale = match &reminder.language {
+            Some(language) => Locale::new(language),
+            None => match commands.get_user_language(reminder.chat_id).await {
+                Ok(Some(language)) => Locale::new(&language),
+                _ => Locale::default(),
+            },
+        };
+        let keyboard =
+            InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+                t!("button.submit-readings", locale = &locale),
+                ACTION_SUBMIT_READINGS,
+            )]);
+        let sent = bot
+            .send_message(
+                ChatId(reminder.chat_id),
+                t!(
+                    "message.reminder",
+                    locale = &locale,
+                    metrics = format_list(
+                        &missing
+                            .iter()
+                            .map(|x| x.name.clone())
+                            .collect::<Vec<String>>(),
+                        &locale
+                    )
+                ),
+            )
+            .reply_markup(keyboard)
+            .await;
+
+        match sent {
+            Ok(_) => {
+                if let Err(error) = commands.mark_reminder_sent(reminder, now.date()).await {
+                    log::error!("Couldn't mark reminder as sent: {}", error);
+                }
+            }
+            Err(error) => log::error!(
+                "Couldn't send reminder to chat {}: {}",
+                reminder.chat_id,
+                error
+            ),
+        }
+    }
+}
+
+/// `/reminder` shows the schedule, `/reminder off` disables it and
+/// `/reminder <first day> <last day> [hour]` sets it.
+pub async fn reminder_command(
+    bot: Bot,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    args: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let args = args.split_whitespace().collect::<Vec<&str>>();
+
+    let text = match args.as_slice() {
+        [] => match commands.get_reminder(chat_id.0).await {
+            Ok(Some(reminder)) => t!(
+                "message.reminder-schedule",
+                locale = &locale,
+                first_day = reminder.first_day,
+                last_day = reminder.last_day,
+                hour = reminder.hour
+            ),
+            Ok(None) => t!("message.reminder-off", locale = &locale),
+            Err(error) => error_message("error.get-reminder", &error, &locale),
+        },
+        ["off"] => match commands.delete_reminder(chat_id.0).await {
+            Ok(_) => t!("message.reminder-off", locale = &locale),
+            Err(error) => error_message("error.disable-reminder", &error, &locale),
+        },
+        [first_day, last_day, rest @ ..] if rest.len() <= 1 => {
+            let parsed = (
+                first_day.parse::<u32>(),
+                last_day.parse::<u32>(),
+                rest.first().map_or(Ok(DEFAULT_HOUR), |x| x.parse::<u32>()),
+            );
+            match parsed {
+                (Ok(first_day), Ok(last_day), Ok(hour)) => {
+                    match commands
+                        .set_reminder(chat_id.0, first_day, last_day, hour, &locale)
+                        .await
+                    {
+                        Ok(_) => t!(
+                            "message.reminder-schedule",
+                            locale = &locale,
+                            first_day = first_day,
+                            last_day = last_day,
+                            hour = hour
+                        ),
+                        Err(error) => error_message("error.set-reminder", &error, &locale),
+                    }
+                }
+                _ => t!("message.reminder-usage", locale = &locale),
+            }
+        }
+        _ => t!("message.reminder-usage", locale = &locale),
+    };
+
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use chrono::Local;
+use teloxide::{prelude::*, requests::Requester, types::ParseMode, utils::html};
+
+use super::{
+    error_message, start_menu, undo::send_undoable, HandlerResult, MyDialogue, SharedCommands,
+    State,
+};
+use crate::{database::structs::Metric, lang::Locale};
+use rust_i18n::t;
+
+/// Asks the final value of the old counter and the initial one of the new
+/// counter of the metric the next value is for.
+async fn ask_replacement_value(
+    bot: &Bot,
+    chat_id: ChatId,
+    metrics: &[Metric],
+    values: &[u32],
+    locale: &Locale,
+) -> HandlerResult {
+    let key = match values.len() % 2 {
+        0 => "message.enter-final-reading",
+        _ => "message.enter-initial-reading",
+    };
+    bot.send_message(
+        chat_id,
+        t!(
+            key,
+            locale = &locale,
+            metric = html::escape(&metrics[values.len() / 2].name)
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// Starts replacing the counters of every metric of the meter.
+pub async fn start_replace_meter(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    chat_id: ChatId,
+    meter_id: u32,
+    locale: &Locale,
+) -> HandlerResult {
+    match commands.list_metrics(meter_id).await {
+        Ok(metrics) if metrics.is_empty() => {
+            bot.send_message(
+                chat_id,
+                t!("message.no-metrics-to-replace", locale = &locale),
+            )
+            .await?;
+        }
+        Ok(metrics) => {
+            ask_replacement_value(&bot, chat_id, &metrics, &[], locale).await?;
+            dialogue
+                .update(State::ReplaceMeter {
+                    metrics,
+                    values: vec![],
+                })
+                .await?;
+            return Ok(());
+        }
+        Err(error) => {
+            bot.send_message(chat_id, error_message("error.list-metrics", &error, locale))
+                .await?;
+        }
+    }
+    start_menu(bot, dialogue, chat_id, locale).await
+}
+
+/// Receives the final and the initial values, one after another for every
+/// metric, and replaces the counters when all of them are known.
+pub async fn receive_replacement_value(
+    bot: Bot,
+    dialogue: MyDialogue,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    (metrics, mut values): (Vec<Metric>, Vec<u32>),
+) -> HandlerResult {
+    match msg.text().map(|x| x.trim().parse::<u32>()) {
+        Some(Ok(value)) => values.push(value),
+        _ => {
+            bot.send_message(msg.chat.id, t!("message.invalid-reading", locale = &locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if values.len() < metrics.len() * 2 {
+        ask_replacement_value(&bot, msg.chat.id, &metrics, &values, &locale).await?;
+        dialogue
+            .update(State::ReplaceMeter { metrics, values })
+            .await?;
+        return Ok(());
+    }
+
+    let replacements = metrics
+        .iter()
+        .zip(values.chunks(2))
+        .map(|(metric, values)| (metric.id, values[0], values[1]))
+        .collect();
+    match commands
+        .replace_meter(replacements, Local::now().naive_local())
+        .await
+    {
+        Ok(action_id) => {
+            let reply = t!("message.meter-replaced", locale = &locale);
+            send_undoable(&bot, msg.chat.id, reply, action_id, &locale).await?;
+        }
+        Err(error) => {
+            bot.send_message(
+                msg.chat.id,
+                error_message("error.replace-meter", &error, &locale),
+            )
+            .await?;
+        }
+    };
+    start_menu(bot, dialogue, msg.chat.id, &locale).await
+}
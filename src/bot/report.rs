@@ -0,0 +1,239 @@
+use chrono::{Local, NaiveDate};
+use teloxide::{prelude::*, requests::Requester, types::ParseMode, utils::html};
+
+use super::{error_message, table::format_table, HandlerResult, SharedCommands};
+use crate::{commands::Report, lang::Locale};
+use rust_i18n::t;
+
+const MONTH_FORMAT: &str = "%m.%Y";
+
+/// Change from `previous` to `current` like `+12.5%`, if there is something
+/// to compare with.
+fn percent_change(current: u64, previous: u64) -> Option<String> {
+    match previous {
+        0 => None,
+        previous => Some(std::format!(
+            "{:+.1}%",
+            (current as f64 - previous as f64) * 100.0 / previous as f64
+        )),
+    }
+}
+
+/// The month of `YYYY-MM`, or the current one if there are no arguments.
+fn parse_month(args: &str) -> Option<NaiveDate> {
+    match args.trim() {
+        "" => Some(Local::now().date_naive()),
+        month => NaiveDate::parse_from_str(&std::format!("{}-01", month), "%Y-%m-%d").ok(),
+    }
+}
+
+/// Cost of every meter in the month and the total, in the order of the report.
+fn bill_lines(report: &Report) -> Vec<(String, u64)> {
+    let mut lines: Vec<(String, u64)> = Vec::new();
+    for line in &report.lines {
+        match lines.last_mut() {
+            Some((meter, cost)) if *meter == line.meter => *cost += line.cost,
+            _ => lines.push((line.meter.clone(), line.cost)),
+        }
+    }
+    lines
+}
+
+fn format_bill(report: &Report, locale: &Locale) -> String {
+    let month = report.month.format(MONTH_FORMAT).to_string();
+    if report.lines.is_empty() {
+        return t!("message.no-report", locale = &locale, month = month);
+    }
+
+    let mut text = t!("message.bill-title", locale = &locale, month = month);
+    for (meter, cost) in bill_lines(report) {
+        text += &std::format!("\n• {}: {}", html::escape(&meter), cost);
+    }
+    text += "\n";
+    text += &t!(
+        "message.report-total",
+        locale = &locale,
+        total = report.total
+    );
+    text
+}
+
+fn format_report(report: &Report, locale: &Locale) -> String {
+    let month = report.month.format(MONTH_FORMAT).to_string();
+    if report.lines.is_empty() {
+        return t!("message.no-report", locale = &locale, month = month);
+    }
+
+    let mut text = t!("message.report-title", locale = &locale, month = month);
+    let mut meters = report
+        .lines
+        .iter()
+        .map(|x| &x.meter)
+        .collect::<Vec<&String>>();
+    meters.dedup();
+    for meter in meters {
+        let mut rows = vec![vec![
+            t!("table.metric", locale = &locale),
+            t!("table.start", locale = &locale),
+            t!("table.end", locale = &locale),
+            t!("table.consumption", locale = &locale),
+            t!("table.rate", locale = &locale),
+            t!("table.cost", locale = &locale),
+            t!("table.change", locale = &locale),
+        ]];
+        for line in report.lines.iter().filter(|x| &x.meter == meter) {
+            rows.push(vec![
+                line.metric.clone(),
+                line.start.to_string(),
+                line.end.to_string(),
+                line.consumption.to_string(),
+                line.rate.to_string(),
+                line.cost.to_string(),
+                line.previous_consumption
+                    .and_then(|x| percent_change(line.consumption as u64, x as u64))
+                    .unwrap_or("-".to_string()),
+            ]);
+        }
+        text += &std::format!(
+            "\n\n<b>{}</b>\n<pre>{}</pre>",
+            html::escape(meter),
+            format_table(&rows)
+        );
+    }
+
+    text += "\n";
+    text += &match report
+        .previous_total
+        .and_then(|x| percent_change(report.total, x))
+    {
+        Some(change) => t!(
+            "message.report-total-change",
+            locale = &locale,
+            total = report.total,
+            change = change
+        ),
+        None => t!(
+            "message.report-total",
+            locale = &locale,
+            total = report.total
+        ),
+    };
+    text
+}
+
+/// `/report [YYYY-MM]` sends the statement of the month, the current one by default.
+pub async fn report_command(
+    bot: Bot,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    args: String,
+) -> HandlerResult {
+    let month = match parse_month(&args) {
+        Some(month) => month,
+        None => {
+            bot.send_message(msg.chat.id, t!("message.report-usage", locale = &locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match commands.monthly_report(month).await {
+        Ok(report) => {
+            bot.send_message(msg.chat.id, format_report(&report, &locale))
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(msg.chat.id, error_message("error.report", &error, &locale))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// `/bill [YYYY-MM]` sends what to pay for every meter in the month, the
+/// current one by default.
+pub async fn bill_command(
+    bot: Bot,
+    commands: SharedCommands,
+    msg: Message,
+    locale: Locale,
+    args: String,
+) -> HandlerResult {
+    let month = match parse_month(&args) {
+        Some(month) => month,
+        None => {
+            bot.send_message(msg.chat.id, t!("message.bill-usage", locale = &locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match commands.monthly_report(month).await {
+        Ok(report) => {
+            bot.send_message(msg.chat.id, format_bill(&report, &locale))
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+        Err(error) => {
+            bot.send_message(msg.chat.id, error_message("error.report", &error, &locale))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bill_lines, parse_month, percent_change};
+    use crate::commands::{Report, ReportLine};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_percent_change() {
+        assert_eq!(percent_change(150, 100), Some("+50.0%".to_string()));
+        assert_eq!(percent_change(170, 270), Some("-37.0%".to_string()));
+        assert_eq!(percent_change(100, 100), Some("+0.0%".to_string()));
+        assert_eq!(percent_change(100, 0), None);
+    }
+
+    #[test]
+    fn test_parse_month() {
+        assert_eq!(
+            parse_month(" 2023-12 "),
+            NaiveDate::from_ymd_opt(2023, 12, 1)
+        );
+        assert!(parse_month("").is_some());
+        assert_eq!(parse_month("2023-13"), None);
+        assert_eq!(parse_month("december"), None);
+    }
+
+    #[test]
+    fn test_bill_lines() {
+        let line = |meter: &str, metric: &str, cost| ReportLine {
+            meter: meter.to_string(),
+            metric: metric.to_string(),
+            start: 0,
+            end: 0,
+            consumption: 0,
+            rate: 0,
+            cost,
+            previous_consumption: None,
+        };
+        let report = Report {
+            month: NaiveDate::from_ymd_opt(2023, 12, 1).unwrap(),
+            lines: vec![
+                line("power", "day", 100),
+                line("power", "night", 50),
+                line("water", "cold", 30),
+            ],
+            total: 180,
+            previous_total: None,
+        };
+        assert_eq!(
+            bill_lines(&report),
+            vec![("power".to_string(), 150), ("water".to_string(), 30)]
+        );
+    }
+}
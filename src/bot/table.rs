@@ -0,0 +1,69 @@
+use teloxide::utils::html;
+
+/// Lays out `rows` as a monospace table for sending inside a `<pre>` block.
+/// The first row is the header; the first column is aligned to the left and
+/// the rest to the right, so numbers line up.
+pub fn format_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(|x| x.len()).max().unwrap_or(0);
+    let widths = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|x| x.get(column))
+                .map(|x| x.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<usize>>();
+
+    let format_row = |row: &Vec<String>| {
+        widths
+            .iter()
+            .enumerate()
+            .map(|(column, width)| {
+                let cell = row.get(column).map(|x| x.as_str()).unwrap_or("");
+                html::escape(&match column {
+                    0 => std::format!("{:<width$}", cell),
+                    _ => std::format!("{:>width$}", cell),
+                })
+            })
+            .collect::<Vec<String>>()
+            .join(" | ")
+    };
+
+    let mut lines = Vec::<String>::new();
+    for (index, row) in rows.iter().enumerate() {
+        lines.push(format_row(row));
+        if index == 0 {
+            lines.push(
+                widths
+                    .iter()
+                    .map(|x| "-".repeat(*x))
+                    .collect::<Vec<String>>()
+                    .join("-+-"),
+            );
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::format_table;
+
+    #[test]
+    fn test_format_table() {
+        let rows = vec![
+            vec!["Metric".to_string(), "Value".to_string()],
+            vec!["day".to_string(), "12345".to_string()],
+            vec!["night & weekend".to_string(), "7".to_string()],
+        ];
+        assert_eq!(
+            format_table(&rows),
+            "Metric          | Value\n\
+             ----------------+------\n\
+             day             | 12345\n\
+             night &amp; weekend |     7"
+        );
+        assert_eq!(format_table(&[]), "");
+    }
+}
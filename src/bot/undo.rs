@@ -0,0 +1,108 @@
+use chrono::Local;
+use teloxide::{
+    prelude::*,
+    requests::Requester,
+    types::{InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup},
+};
+
+use super::{error_message, HandlerResult, SharedCommands};
+use crate::{database::ops::UndoActionId, lang::Locale};
+use rust_i18n::t;
+
+const ACTION_UNDO: &str = "undo";
+
+/// Id of the action an undo button reverts, like `undo:12`.
+fn parse_undo_action(data: &str) -> Option<u32> {
+    match data.split(':').collect::<Vec<&str>>().as_slice() {
+        [ACTION_UNDO, action_id] => action_id.parse().ok(),
+        _ => None,
+    }
+}
+
+fn undo_action(action_id: u32) -> String {
+    std::format!("{}:{}", ACTION_UNDO, action_id)
+}
+
+/// Whether the callback data is of an undo button, which works in any state.
+pub fn is_undo_action(data: &str) -> bool {
+    parse_undo_action(data).is_some()
+}
+
+/// Button reverting the change of the action, if the change recorded one.
+pub fn undo_action_button(
+    action_id: UndoActionId,
+    locale: &Locale,
+) -> Option<InlineKeyboardButton> {
+    action_id.map(|x| {
+        InlineKeyboardButton::callback(t!("button.undo", locale = &locale), undo_action(x))
+    })
+}
+
+/// Sends the confirmation of a change just made, with a button undoing it.
+pub async fn send_undoable(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: String,
+    action_id: UndoActionId,
+    locale: &Locale,
+) -> HandlerResult {
+    let request = bot.send_message(chat_id, text);
+    match undo_action_button(action_id, locale) {
+        Some(button) => {
+            request
+                .reply_markup(InlineKeyboardMarkup::default().append_row(vec![button]))
+                .await?
+        }
+        None => request.await?,
+    };
+    Ok(())
+}
+
+/// Reverts the change and removes the undo button, keeping the other buttons
+/// of the message.
+pub async fn undo_button(
+    bot: Bot,
+    commands: SharedCommands,
+    q: CallbackQuery,
+    locale: Locale,
+) -> HandlerResult {
+    let (msg, action_id) = match (&q.message, q.data.as_deref().and_then(parse_undo_action)) {
+        (Some(msg), Some(action_id)) => (msg, action_id),
+        _ => return Ok(()),
+    };
+    log::debug!("Undo button pressed: {}", action_id);
+
+    let rows = msg
+        .reply_markup()
+        .map(|x| x.inline_keyboard.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|row| {
+            !row.iter().any(|button| {
+                matches!(&button.kind, InlineKeyboardButtonKind::CallbackData(data) if is_undo_action(data))
+            })
+        })
+        .collect::<Vec<Vec<InlineKeyboardButton>>>();
+    bot.edit_message_reply_markup(msg.chat.id, msg.id)
+        .reply_markup(InlineKeyboardMarkup::new(rows))
+        .await?;
+
+    let reply = match commands.undo(action_id, Local::now().naive_local()).await {
+        Ok(_) => t!("message.undone", locale = &locale),
+        Err(error) => error_message("error.undo", &error, &locale),
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_undo_action, parse_undo_action, undo_action};
+
+    #[test]
+    fn test_undo_action_round_trip() {
+        assert_eq!(parse_undo_action(&undo_action(12)), Some(12));
+        assert!(!is_undo_action("undo:x"));
+        assert!(!is_undo_action("history:0"));
+    }
+}
@@ -0,0 +1,282 @@
+use std::{fmt::Display, net::SocketAddr};
+
+use teloxide::update_listeners::webhooks::Options;
+use url::Url;
+
+/// Public HTTPS address Telegram sends the updates to, long polling is used
+/// if it isn't set.
+const WEBHOOK_URL_VAR: &str = "METERS_WEBHOOK_URL";
+/// Local address the HTTP server listens on, behind a proxy terminating TLS.
+const WEBHOOK_ADDRESS_VAR: &str = "METERS_WEBHOOK_ADDRESS";
+/// Token Telegram sends in the `X-Telegram-Bot-Api-Secret-Token` header, a
+/// random one is generated on every start if it isn't set.
+const WEBHOOK_SECRET_VAR: &str = "METERS_WEBHOOK_SECRET";
+const DEFAULT_WEBHOOK_ADDRESS: &str = "0.0.0.0:8443";
+
+/// Settings of receiving updates by a webhook instead of long polling.
+#[derive(Debug, PartialEq)]
+pub struct WebhookConfig {
+    pub url: Url,
+    pub address: SocketAddr,
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebhookError {
+    Url(String),
+    Address(String),
+    /// Telegram allows 1-256 characters `A-Z`, `a-z`, `0-9`, `_` and `-`.
+    Secret,
+}
+
+impl Display for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookError::Url(url) => write!(f, "Invalid webhook URL {}", url),
+            WebhookError::Address(address) => write!(f, "Invalid listening address {}", address),
+            WebhookError::Secret => write!(f, "Invalid secret token"),
+        }
+    }
+}
+
+fn is_valid_secret(secret: &str) -> bool {
+    (1..=256).contains(&secret.len())
+        && secret
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '_' || x == '-')
+}
+
+impl WebhookConfig {
+    pub fn new(
+        url: &str,
+        address: Option<&str>,
+        secret: Option<String>,
+    ) -> Result<Self, WebhookError> {
+        let url = match Url::parse(url) {
+            Ok(parsed) if parsed.scheme() == "https" => parsed,
+            _ => return Err(WebhookError::Url(url.to_string())),
+        };
+        let address = address.unwrap_or(DEFAULT_WEBHOOK_ADDRESS);
+        let address = match address.parse::<SocketAddr>() {
+            Ok(address) => address,
+            Err(_) => return Err(WebhookError::Address(address.to_string())),
+        };
+        match secret {
+            Some(secret) if !is_valid_secret(&secret) => Err(WebhookError::Secret),
+            secret => Ok(WebhookConfig {
+                url,
+                address,
+                secret,
+            }),
+        }
+    }
+
+    /// Reads the settings from the environment, none meaning long polling.
+    pub fn from_env() -> Result<Option<Self>, WebhookError> {
+        match std::env::var(WEBHOOK_URL_VAR) {
+            Ok(url) => Self::new(
+                &url,
+                std::env::var(WEBHOOK_ADDRESS_VAR).ok().as_deref(),
+                std::env::var(WEBHOOK_SECRET_VAR).ok(),
+            )
+            .map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn options(&self) -> Options {
+        let options = Options::new(self.address, self.url.clone());
+        match &self.secret {
+            Some(secret) => options.secret_token(secret.clone()),
+            None => options,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{WebhookConfig, WebhookError};
+    use crate::bot::{schema, State};
+    use crate::commands::Commands;
+    use crate::database::create::create_tables_if_do_not_exist;
+    use axum::{
+        extract::State as Extract,
+        http::{StatusCode, Uri},
+        Json, Router,
+    };
+    use hyper::{Body, Client, Request};
+    use rust_i18n::t;
+    use serde_json::{json, Value};
+    use std::{net::SocketAddr, sync::Arc, time::Duration};
+    use teloxide::{
+        dispatching::dialogue::InMemStorage, error_handlers::LoggingErrorHandler, prelude::*,
+        update_listeners::webhooks,
+    };
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+    use url::Url;
+
+    /// A message as Telegram sends it.
+    const UPDATE: &str = r#"{
+        "update_id": 123456789,
+        "message": {
+            "message_id": 42,
+            "date": 1700000000,
+            "chat": {"id": 67647522, "type": "private", "first_name": "Test"},
+            "from": {"id": 67647522, "is_bot": false, "first_name": "Test", "language_code": "uk"},
+            "text": "/meters",
+            "entities": [{"offset": 0, "length": 7, "type": "bot_command"}]
+        }
+    }"#;
+
+    #[test]
+    fn test_webhook_config() {
+        let config = WebhookConfig::new(
+            "https://example.com/meters",
+            None,
+            Some("abc_DEF-123".to_string()),
+        )
+        .unwrap();
+        assert_eq!(config.address.to_string(), "0.0.0.0:8443");
+        assert_eq!(config.url.path(), "/meters");
+
+        assert_eq!(
+            WebhookConfig::new("http://example.com", None, None),
+            Err(WebhookError::Url("http://example.com".to_string()))
+        );
+        assert_eq!(
+            WebhookConfig::new("https://example.com", Some("localhost"), None),
+            Err(WebhookError::Address("localhost".to_string()))
+        );
+        assert_eq!(
+            WebhookConfig::new("https://example.com", None, Some("a b".to_string())),
+            Err(WebhookError::Secret)
+        );
+        assert_eq!(
+            WebhookConfig::new("https://example.com", None, Some("".to_string())),
+            Err(WebhookError::Secret)
+        );
+    }
+
+    /// Method and parameters of a request of the bot to Telegram.
+    type ApiRequest = (String, Value);
+
+    /// Answers the requests of the bot the way Telegram does, passing them on
+    /// to the test.
+    async fn answer(
+        Extract(requests): Extract<UnboundedSender<ApiRequest>>,
+        uri: Uri,
+        body: String,
+    ) -> Json<Value> {
+        let method = uri.path().rsplit('/').next().unwrap_or("").to_string();
+        // the parameters are JSON, or a multipart form kept as it is
+        let parameters = serde_json::from_str::<Value>(&body).unwrap_or(Value::String(body));
+        let result = match method.as_str() {
+            "GetMe" => json!({
+                "id": 1,
+                "is_bot": true,
+                "first_name": "Meters",
+                "username": "meters_bot",
+                "can_join_groups": true,
+                "can_read_all_group_messages": false,
+                "supports_inline_queries": false
+            }),
+            "SendMessage" => json!({
+                "message_id": 43,
+                "date": 1700000000,
+                "chat": {"id": parameters["chat_id"], "type": "private", "first_name": "Test"},
+                "text": parameters["text"]
+            }),
+            _ => json!(true),
+        };
+        requests.send((method, parameters)).unwrap();
+        Json(json!({"ok": true, "result": result}))
+    }
+
+    /// Address of a fake Telegram Bot API server.
+    fn fake_telegram(requests: UnboundedSender<ApiRequest>) -> SocketAddr {
+        let router = Router::new().fallback(answer).with_state(requests);
+        let server =
+            axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(router.into_make_service());
+        let address = server.local_addr();
+        tokio::spawn(server);
+        address
+    }
+
+    async fn next_request(requests: &mut UnboundedReceiver<ApiRequest>) -> ApiRequest {
+        tokio::time::timeout(Duration::from_secs(10), requests.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_webhook_dispatches_updates() {
+        let (sender, mut requests) = unbounded_channel();
+        let api = fake_telegram(sender);
+        let bot = Bot::new("123:token")
+            .set_api_url(Url::parse(&std::format!("http://{}/", api)).unwrap());
+
+        // a port nothing listens on yet
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let config = WebhookConfig::new(
+            "https://example.com/meters/webhook",
+            Some(&address.to_string()),
+            Some("secret".to_string()),
+        )
+        .unwrap();
+        let listener = webhooks::axum(bot.clone(), config.options()).await.unwrap();
+        let (method, parameters) = next_request(&mut requests).await;
+        assert_eq!(method, "SetWebhook");
+        let form = parameters.as_str().unwrap();
+        assert!(form.contains("name=\"url\"\r\n\r\nhttps://example.com/meters/webhook\r\n"));
+        assert!(form.contains("name=\"secret_token\"\r\n\r\nsecret\r\n"));
+
+        let commands = Arc::new(Commands::in_memory().await.unwrap());
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+        let mut dispatcher = Dispatcher::builder(bot, schema())
+            .dependencies(dptree::deps![InMemStorage::<State>::new(), commands])
+            .build();
+        tokio::spawn(async move {
+            dispatcher
+                .dispatch_with_listener(listener, LoggingErrorHandler::new())
+                .await
+        });
+
+        let client = Client::new();
+        let send = |secret: Option<&str>| {
+            let request = Request::post(std::format!("http://{}/meters/webhook", address))
+                .header("content-type", "application/json");
+            let request = match secret {
+                Some(secret) => request.header("x-telegram-bot-api-secret-token", secret),
+                None => request,
+            };
+            client.request(request.body(Body::from(UPDATE)).unwrap())
+        };
+        // the server of the webhook starts in the background
+        let mut response = send(None).await;
+        for _ in 0..50 {
+            if response.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            response = send(None).await;
+        }
+        assert_eq!(response.unwrap().status(), StatusCode::UNAUTHORIZED);
+        let response = send(Some("wrong")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = send(Some("secret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let reply = loop {
+            let (method, parameters) = next_request(&mut requests).await;
+            if method == "SendMessage" {
+                break parameters;
+            }
+        };
+        assert_eq!(reply["chat_id"], 67647522);
+        assert_eq!(reply["text"], t!("message.no-meters", locale = "uk"));
+    }
+}
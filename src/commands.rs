@@ -1,18 +1,247 @@
-use crate::database::ops::Operations;
-use crate::database::structs::Meter;
-use tokio_rusqlite::Connection;
+use crate::database::ops::{Operations, UndoActionId, UNDO_MINUTES};
+use crate::database::query::{Order, Where, WhereExprOperator};
+use crate::database::structs::{
+    AuditRecord, FromRow, Meter, Metric, MetricAlert, MetricCapacity, MetricReplacement,
+    MetricValue, ReadingPhoto, Reminder, TableName, UndoAction, UndoStep, UserSettings,
+};
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Timelike};
+use rust_i18n::t;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::path::PathBuf;
+use tokio_rusqlite::{Connection, Error};
 
-pub type CommandResult<T> = Result<T, String>;
+pub type CommandResult<T> = Result<T, CommandError>;
 pub type CommandResultNoValue = CommandResult<()>;
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    Database(String),
+    MeterExists(String),
+    MeterNotFound(u32),
+    /// The meter is archived, so its metrics take no new readings.
+    MeterArchived(u32),
+    MetricNotFound(u32),
+    InvalidReminder,
+    Csv(String),
+    File(String),
+    InvalidBackup,
+    InvalidAlertFactor,
+    InvalidDigits,
+    ReadingNotFound(u32),
+    /// The value is less than the given previous reading.
+    ReadingTooSmall(u32),
+    /// The value is greater than the given next reading, or than the counter
+    /// can show.
+    ReadingTooLarge(u32),
+    /// The change doesn't exist, is already undone or was made by someone else.
+    CannotUndo(u32),
+    UndoExpired(u32),
+    /// The data changed by the action has been changed again since then.
+    UndoConflict(u32),
+}
+
+impl CommandError {
+    /// Explanation for the user in the language of `locale`, without the
+    /// technical details that go to the log.
+    pub fn localize(&self, locale: &str) -> String {
+        match self {
+            CommandError::Database(_) => t!("error.database", locale = locale),
+            CommandError::MeterExists(name) => {
+                t!("error.meter-exists", locale = locale, name = name)
+            }
+            CommandError::MeterNotFound(_) => t!("error.meter-not-found", locale = locale),
+            CommandError::MeterArchived(_) => t!("error.meter-archived", locale = locale),
+            CommandError::MetricNotFound(_) => t!("error.metric-not-found", locale = locale),
+            CommandError::InvalidReminder => t!("error.invalid-reminder", locale = locale),
+            CommandError::Csv(_) => t!("error.csv", locale = locale),
+            CommandError::File(_) => t!("error.file", locale = locale),
+            CommandError::InvalidBackup => t!("error.invalid-backup", locale = locale),
+            CommandError::InvalidAlertFactor => {
+                t!("error.invalid-alert-factor", locale = locale)
+            }
+            CommandError::InvalidDigits => {
+                t!("error.invalid-digits", locale = locale, max = MAX_DIGITS)
+            }
+            CommandError::ReadingNotFound(_) => t!("error.reading-not-found", locale = locale),
+            CommandError::ReadingTooSmall(min) => {
+                t!("error.reading-too-small", locale = locale, min = min)
+            }
+            CommandError::ReadingTooLarge(max) => {
+                t!("error.reading-too-large", locale = locale, max = max)
+            }
+            CommandError::CannotUndo(_) => t!("error.cannot-undo", locale = locale),
+            CommandError::UndoExpired(_) => t!(
+                "error.undo-expired",
+                locale = locale,
+                minutes = UNDO_MINUTES
+            ),
+            CommandError::UndoConflict(_) => t!("error.undo-conflict", locale = locale),
+        }
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Database(error) => write!(f, "Database error: {}", error),
+            CommandError::MeterExists(name) => write!(f, "Meter with name {} already exists", name),
+            CommandError::MeterNotFound(id) => write!(f, "Meter with id {} doesn't exist", id),
+            CommandError::MeterArchived(id) => write!(f, "Meter with id {} is archived", id),
+            CommandError::MetricNotFound(id) => write!(f, "Metric with id {} doesn't exist", id),
+            CommandError::InvalidReminder => write!(f, "Invalid reminder schedule"),
+            CommandError::Csv(error) => write!(f, "CSV error: {}", error),
+            CommandError::File(error) => write!(f, "File error: {}", error),
+            CommandError::InvalidBackup => write!(f, "Invalid backup file"),
+            CommandError::InvalidAlertFactor => write!(f, "Invalid alert factor"),
+            CommandError::InvalidDigits => write!(f, "Invalid number of digits"),
+            CommandError::ReadingNotFound(id) => write!(f, "Reading with id {} doesn't exist", id),
+            CommandError::ReadingTooSmall(min) => write!(f, "Reading is less than {}", min),
+            CommandError::ReadingTooLarge(max) => write!(f, "Reading is greater than {}", max),
+            CommandError::CannotUndo(id) => write!(f, "Action {} can't be undone", id),
+            CommandError::UndoExpired(id) => write!(f, "Action {} is too old to undo", id),
+            CommandError::UndoConflict(id) => {
+                write!(f, "Data of action {} has been changed since", id)
+            }
+        }
+    }
+}
+
+impl From<Error> for CommandError {
+    fn from(error: Error) -> Self {
+        CommandError::Database(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(error: std::io::Error) -> Self {
+        CommandError::File(error.to_string())
+    }
+}
+
+impl From<csv::Error> for CommandError {
+    fn from(error: csv::Error) -> Self {
+        CommandError::Csv(error.to_string())
+    }
+}
+
+const CSV_HEADER: [&str; 5] = ["meter", "metric", "value", "rate", "timestamp"];
+const CSV_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const CSV_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A reading read from a CSV file, in the format of [`Commands::export_readings`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub line: u64,
+    pub meter: String,
+    pub metric: String,
+    pub value: u32,
+    pub rate: u32,
+    pub added: NaiveDateTime,
+}
+
+/// Why a line of a CSV file can't be imported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportError {
+    Header,
+    Format,
+    EmptyName,
+    Value,
+    Rate,
+    Timestamp,
+}
+
+/// What importing a CSV file would do, shown to the user before importing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportPlan {
+    pub rows: Vec<ImportRow>,
+    pub errors: Vec<(u64, ImportError)>,
+    pub missing_meters: Vec<String>,
+    /// Pairs of meter and metric names, for meters that exist or not.
+    pub missing_metrics: Vec<(String, String)>,
+    /// Rows having the same metric and timestamp as an existing value.
+    pub duplicates: usize,
+}
+
+pub const DEFAULT_ALERT_FACTOR: f64 = 2.0;
+/// Counters with more digits can't be stored as `u32`.
+pub const MAX_DIGITS: u32 = 9;
+/// How many days before the previous value the average consumption is taken for.
+const RECENT_DAYS: i64 = 90;
+
 pub struct Commands {
     ops: Operations,
 }
+
+/// What a value entered for a metric looks like compared to its last value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReadingCheck {
+    Ok,
+    /// The value has more digits than the counter.
+    TooLarge(u32),
+    /// The value is less than the last one, which is given, as if the counter
+    /// has started from zero again.
+    Rollover(u32),
+}
+
+/// A submitted value implying a daily consumption more than `factor` times
+/// above the recent average.
+#[derive(Debug, Clone)]
+pub struct ConsumptionAlert {
+    pub metric: Metric,
+    pub daily: f64,
+    pub average: f64,
+    pub factor: f64,
+}
+
+/// Readings of a metric in a month, see [`Commands::monthly_report`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportLine {
+    pub meter: String,
+    pub metric: String,
+    pub start: u32,
+    pub end: u32,
+    pub consumption: u32,
+    pub rate: u32,
+    pub cost: u64,
+    /// Consumption in the previous month, if there were readings.
+    pub previous_consumption: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub month: NaiveDate,
+    pub lines: Vec<ReportLine>,
+    pub total: u64,
+    /// Total cost in the previous month, if there were readings.
+    pub previous_total: Option<u64>,
+}
+
+/// A slice of a longer list together with the length of the whole list.
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub offset: usize,
+    pub total: usize,
+}
 impl Commands {
     pub async fn in_memory() -> CommandResult<Self> {
         match Operations::in_memory().await {
             Ok(ops) => Ok(Commands { ops }),
-            Err(error) => Err(error.to_string()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn open(path: PathBuf) -> CommandResult<Self> {
+        match Operations::open(path).await {
+            Ok(ops) => Ok(Commands { ops }),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// The same commands, where the changes are recorded as made by the user.
+    pub fn for_user(&self, user_id: i64) -> Self {
+        Commands {
+            ops: self.ops.for_user(user_id),
         }
     }
 
@@ -20,43 +249,1384 @@ impl Commands {
         self.ops.get_connection()
     }
 
-    pub async fn add_meter(&self, name: &str) -> CommandResultNoValue {
+    /// Contents of an SQLite file with a snapshot of the whole database.
+    pub async fn backup(&self) -> CommandResult<Vec<u8>> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("backup.sqlite");
+        if let Err(error) = self.ops.backup(path.clone()).await {
+            return Err(error.into());
+        }
+        Ok(std::fs::read(path)?)
+    }
+
+    /// Replaces the whole database with the one made by [`Commands::backup`].
+    pub async fn restore(&self, backup: Vec<u8>) -> CommandResultNoValue {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("backup.sqlite");
+        std::fs::write(&path, backup)?;
+        match self.ops.restore(path).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(CommandError::InvalidBackup),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn add_meter(&self, name: &str) -> CommandResult<UndoActionId> {
         let meter_exists = match self.ops.exists_by_name::<Meter>(name.to_string()).await {
             Ok(exists) => exists,
-            Err(error) => return Err(error.to_string()),
+            Err(error) => return Err(error.into()),
         };
         if meter_exists {
-            return Err(std::format!("Meter with name {} already exists", name));
+            return Err(CommandError::MeterExists(name.to_string()));
         }
 
         match self.ops.insert(Meter::new(name)).await {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Deletes the meter together with its metrics and their values.
+    pub async fn delete_meter(&self, id: u32) -> CommandResult<UndoActionId> {
+        match self
+            .ops
+            .transaction(move |transaction| {
+                let meter_metrics = Where::new("meter_id", WhereExprOperator::Equal, id);
+                for metric in transaction.find::<Metric>(meter_metrics.clone())? {
+                    let metric_rows = Where::new("metric_id", WhereExprOperator::Equal, metric.id);
+                    transaction.delete_where::<MetricValue>(metric_rows.clone())?;
+                    transaction.delete_where::<MetricAlert>(metric_rows.clone())?;
+                    transaction.delete_where::<MetricCapacity>(metric_rows.clone())?;
+                    transaction.delete_where::<MetricReplacement>(metric_rows.clone())?;
+                    transaction.delete_where::<ReadingPhoto>(metric_rows)?;
+                }
+                transaction.delete_where::<Metric>(meter_metrics)?;
+                transaction.delete_by_id::<Meter>(id)?;
+                Ok(transaction.undo_action_id())
+            })
+            .await
+        {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Meters in use, without the archived ones.
+    pub async fn list_meters(&self) -> CommandResult<Vec<Meter>> {
+        match self.ops.find::<Meter>(archived_meters(false)).await {
+            Ok(rows) => Ok(rows),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn list_meters_page(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> CommandResult<Page<Meter>> {
+        self.page(
+            Some(archived_meters(false)),
+            "id",
+            Order::Ascending,
+            offset,
+            limit,
+        )
+        .await
+    }
+
+    pub async fn list_archived_meters_page(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> CommandResult<Page<Meter>> {
+        self.page(
+            Some(archived_meters(true)),
+            "id",
+            Order::Ascending,
+            offset,
+            limit,
+        )
+        .await
+    }
+
+    /// Hides the meter from the menus and reminders, keeping its history.
+    pub async fn archive_meter(&self, id: u32) -> CommandResult<UndoActionId> {
+        self.set_meter_archived(id, true).await
+    }
+
+    /// Brings an archived meter back into use.
+    pub async fn restore_meter(&self, id: u32) -> CommandResult<UndoActionId> {
+        self.set_meter_archived(id, false).await
+    }
+
+    async fn set_meter_archived(&self, id: u32, archived: bool) -> CommandResult<UndoActionId> {
+        let mut meter = self.get_meter(id).await?;
+        meter.archived = archived;
+        match self.ops.update(meter).await {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn list_metrics_page(
+        &self,
+        meter_id: u32,
+        offset: usize,
+        limit: usize,
+    ) -> CommandResult<Page<Metric>> {
+        self.page(
+            Some(Where::new("meter_id", WhereExprOperator::Equal, meter_id)),
+            "id",
+            Order::Ascending,
+            offset,
+            limit,
+        )
+        .await
+    }
+
+    /// Values of the metric, newest first.
+    pub async fn list_readings_page(
+        &self,
+        metric_id: u32,
+        offset: usize,
+        limit: usize,
+    ) -> CommandResult<Page<MetricValue>> {
+        self.page(
+            Some(Where::new("metric_id", WhereExprOperator::Equal, metric_id)),
+            "added",
+            Order::Descending,
+            offset,
+            limit,
+        )
+        .await
+    }
+
+    async fn page<T: TableName + FromRow + Send + Sync + 'static>(
+        &self,
+        condition: Option<Where>,
+        order_by: &str,
+        order: Order,
+        offset: usize,
+        limit: usize,
+    ) -> CommandResult<Page<T>> {
+        let total = match &condition {
+            Some(condition) => self.ops.count_where::<T>(condition.clone()).await,
+            None => self.ops.count::<T>().await,
+        };
+        let total = match total {
+            Ok(total) => total,
+            Err(error) => return Err(error.into()),
+        };
+
+        match self
+            .ops
+            .find_page::<T>(condition, order_by, order, offset, limit)
+            .await
+        {
+            Ok(items) => Ok(Page {
+                items,
+                offset,
+                total,
+            }),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Changes of the data, the latest first.
+    pub async fn list_audit_page(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> CommandResult<Page<AuditRecord>> {
+        self.page(None, "id", Order::Descending, offset, limit)
+            .await
+    }
+
+    /// Reverts the change, unless it's too old or the data it changed has been
+    /// changed again by a later action.
+    pub async fn undo(&self, action_id: u32, now: NaiveDateTime) -> CommandResultNoValue {
+        let action = match self.ops.get_by_id::<UndoAction>(action_id).await {
+            Ok(Some(action)) => action,
+            // expired actions are deleted
+            Ok(None) => return Err(CommandError::UndoExpired(action_id)),
+            Err(error) => return Err(error.into()),
+        };
+        if action.undone || action.user_id.is_none() || action.user_id != self.ops.user_id() {
+            return Err(CommandError::CannotUndo(action_id));
+        }
+        if now - action.created > Duration::minutes(UNDO_MINUTES) {
+            return Err(CommandError::UndoExpired(action_id));
+        }
+
+        let steps = match self
+            .ops
+            .find::<UndoStep>(Where::new("action_id", WhereExprOperator::Equal, action_id))
+            .await
+        {
+            Ok(steps) => steps,
+            Err(error) => return Err(error.into()),
+        };
+        for step in steps {
+            match self
+                .ops
+                .count_where::<UndoStep>(
+                    Where::new("action_id", WhereExprOperator::Greater, action_id)
+                        .and("entity", WhereExprOperator::Equal, &step.entity)
+                        .and("entity_id", WhereExprOperator::Equal, step.entity_id),
+                )
+                .await
+            {
+                Ok(0) => {}
+                Ok(_) => return Err(CommandError::UndoConflict(action_id)),
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        match self.ops.undo(action_id).await {
             Ok(_) => Ok(()),
-            Err(error) => Err(std::format!(
-                "Couldn't add meter to the database: {}",
-                error
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn get_meter(&self, id: u32) -> CommandResult<Meter> {
+        match self.ops.get_by_id::<Meter>(id).await {
+            Ok(Some(meter)) => Ok(meter),
+            Ok(None) => Err(CommandError::MeterNotFound(id)),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn get_metric(&self, id: u32) -> CommandResult<Metric> {
+        match self.ops.get_by_id::<Metric>(id).await {
+            Ok(Some(metric)) => Ok(metric),
+            Ok(None) => Err(CommandError::MetricNotFound(id)),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn list_metrics(&self, meter_id: u32) -> CommandResult<Vec<Metric>> {
+        match self
+            .ops
+            .find::<Metric>(Where::new("meter_id", WhereExprOperator::Equal, meter_id))
+            .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Stores the `(metric id, value)` pairs at once, all dated `added`, and
+    /// returns alerts for the values implying abnormal consumption, along with
+    /// the action undoing the submission.
+    pub async fn submit_readings(
+        &self,
+        readings: Vec<(u32, u32)>,
+        added: NaiveDateTime,
+    ) -> CommandResult<(Vec<ConsumptionAlert>, UndoActionId)> {
+        self.submit_readings_with_photos(
+            readings
+                .into_iter()
+                .map(|(metric_id, value)| (metric_id, value, None))
+                .collect(),
+            added,
+        )
+        .await
+    }
+
+    /// Same as [`Commands::submit_readings`], where a value may come with a
+    /// photo of the counter, which gets linked to it.
+    pub async fn submit_readings_with_photos(
+        &self,
+        readings: Vec<(u32, u32, Option<ReadingPhoto>)>,
+        added: NaiveDateTime,
+    ) -> CommandResult<(Vec<ConsumptionAlert>, UndoActionId)> {
+        let mut alerts = Vec::new();
+        for (metric_id, value, _) in &readings {
+            if let Some(alert) = self.consumption_alert(*metric_id, *value, added).await? {
+                alerts.push(alert);
+            }
+        }
+
+        match self
+            .ops
+            .transaction(move |transaction| {
+                for (metric_id, value, photo) in readings {
+                    let metric_value_id =
+                        transaction.insert(MetricValue::new(metric_id, value, &added))?;
+                    if let Some(mut photo) = photo {
+                        photo.metric_value_id = metric_value_id;
+                        photo.metric_id = metric_id;
+                        transaction.insert(photo)?;
+                    }
+                }
+                Ok(transaction.undo_action_id())
+            })
+            .await
+        {
+            Ok(action_id) => Ok((alerts, action_id)),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn get_reading(&self, id: u32) -> CommandResult<MetricValue> {
+        match self.ops.get_by_id::<MetricValue>(id).await {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => Err(CommandError::ReadingNotFound(id)),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Corrects the value of a reading, which must stay between the readings
+    /// around it, unless the counter has started from zero between them.
+    pub async fn update_reading(&self, id: u32, value: u32) -> CommandResult<UndoActionId> {
+        let mut reading = self.get_reading(id).await?;
+        let digits = self.get_digits(reading.metric_id).await?;
+        if let Some(digits) = digits {
+            let largest = (10u64.pow(digits) - 1) as u32;
+            if value > largest {
+                return Err(CommandError::ReadingTooLarge(largest));
+            }
+        }
+
+        let readings = self.metric_history(reading.metric_id).await?;
+        if let Some(position) = readings.iter().position(|x| x.id == Some(id)) {
+            // a smaller value after a larger one is a rollover if it may be
+            let rollover = |from: u32, to: u32| digits.is_some() && to < from;
+            if let Some(previous) = position.checked_sub(1).map(|x| readings[x]) {
+                if !rollover(previous.value, reading.value) && value < previous.value {
+                    return Err(CommandError::ReadingTooSmall(previous.value));
+                }
+            }
+            if let Some(next) = readings.get(position + 1) {
+                if !rollover(reading.value, next.value) && value > next.value {
+                    return Err(CommandError::ReadingTooLarge(next.value));
+                }
+            }
+        }
+
+        reading.value = value;
+        match self.ops.update(reading).await {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Deletes a reading together with the photo of the counter taken for it.
+    pub async fn delete_reading(&self, id: u32) -> CommandResult<UndoActionId> {
+        match self
+            .ops
+            .transaction(move |transaction| {
+                transaction.delete_where::<ReadingPhoto>(Where::new(
+                    "metric_value_id",
+                    WhereExprOperator::Equal,
+                    id,
+                ))?;
+                transaction.delete_by_id::<MetricValue>(id)?;
+                Ok(transaction.undo_action_id())
+            })
+            .await
+        {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Photo of the counter submitted with the value, if there is one.
+    pub async fn get_reading_photo(
+        &self,
+        metric_value_id: u32,
+    ) -> CommandResult<Option<ReadingPhoto>> {
+        match self
+            .ops
+            .find::<ReadingPhoto>(Where::new(
+                "metric_value_id",
+                WhereExprOperator::Equal,
+                metric_value_id,
+            ))
+            .await
+        {
+            Ok(rows) => Ok(rows.into_iter().next()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Photos of the counter of the metric submitted with its values.
+    pub async fn list_reading_photos(&self, metric_id: u32) -> CommandResult<Vec<ReadingPhoto>> {
+        match self
+            .ops
+            .find::<ReadingPhoto>(Where::new("metric_id", WhereExprOperator::Equal, metric_id))
+            .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn consumption_alert(
+        &self,
+        metric_id: u32,
+        value: u32,
+        added: NaiveDateTime,
+    ) -> CommandResult<Option<ConsumptionAlert>> {
+        let factor = match self.get_alert_factor(metric_id).await? {
+            Some(factor) => factor,
+            None => return Ok(None),
+        };
+        let readings = self.metric_history(metric_id).await?;
+        let digits = self.get_digits(metric_id).await?;
+        match daily_consumption(&readings, value, added, digits) {
+            Some((daily, average)) if average > 0.0 && daily > average * factor => {
+                Ok(Some(ConsumptionAlert {
+                    metric: self.get_metric(metric_id).await?,
+                    daily,
+                    average,
+                    factor,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Number of digits of the counter of the metric, if it is declared.
+    pub async fn get_digits(&self, metric_id: u32) -> CommandResult<Option<u32>> {
+        match self
+            .ops
+            .find::<MetricCapacity>(Where::new("metric_id", WhereExprOperator::Equal, metric_id))
+            .await
+        {
+            Ok(rows) => Ok(rows.into_iter().next().map(|x| x.digits)),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Declares the number of digits of the counter, or that it is unknown.
+    pub async fn set_digits(
+        &self,
+        metric_id: u32,
+        digits: Option<u32>,
+    ) -> CommandResult<UndoActionId> {
+        if digits.is_some_and(|x| !(1..=MAX_DIGITS).contains(&x)) {
+            return Err(CommandError::InvalidDigits);
+        }
+
+        match self
+            .ops
+            .transaction(move |transaction| {
+                transaction.delete_where::<MetricCapacity>(Where::new(
+                    "metric_id",
+                    WhereExprOperator::Equal,
+                    metric_id,
+                ))?;
+                if let Some(digits) = digits {
+                    transaction.insert(MetricCapacity::new(metric_id, digits))?;
+                }
+                Ok(transaction.undo_action_id())
+            })
+            .await
+        {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Checks the value entered for the metric against its counter and its
+    /// last value. A rollover is only probable for counters with known digits.
+    pub async fn check_reading(&self, metric_id: u32, value: u32) -> CommandResult<ReadingCheck> {
+        match self.get_digits(metric_id).await? {
+            Some(digits) => Ok(check_value(
+                value,
+                digits,
+                &self.metric_history(metric_id).await?,
             )),
+            None => Ok(ReadingCheck::Ok),
+        }
+    }
+
+    /// Stores the value of a metric of a meter in use dated `added`, checked
+    /// like in [`Commands::check_reading`] in the same transaction, so that no
+    /// other value comes in between. A value over the digits of the counter is
+    /// refused, so is a rollover unless it is confirmed with `rollover`.
+    pub async fn submit_checked_reading(
+        &self,
+        metric_id: u32,
+        value: u32,
+        rollover: bool,
+        added: NaiveDateTime,
+    ) -> CommandResult<(Vec<ConsumptionAlert>, UndoActionId)> {
+        let alert = self.consumption_alert(metric_id, value, added).await?;
+        let result = self
+            .ops
+            .transaction(move |transaction| {
+                let by_id = |id: u32| Where::new("id", WhereExprOperator::Equal, id);
+                let metric = match transaction.find::<Metric>(by_id(metric_id))?.pop() {
+                    Some(metric) => metric,
+                    None => return Ok(Err(CommandError::MetricNotFound(metric_id))),
+                };
+                match transaction.find::<Meter>(by_id(metric.meter_id))?.pop() {
+                    Some(meter) if meter.archived => {
+                        return Ok(Err(CommandError::MeterArchived(meter.id)))
+                    }
+                    Some(_) => {}
+                    None => return Ok(Err(CommandError::MeterNotFound(metric.meter_id))),
+                }
+
+                let metric_rows = Where::new("metric_id", WhereExprOperator::Equal, metric_id);
+                let digits = transaction
+                    .find::<MetricCapacity>(metric_rows.clone())?
+                    .pop()
+                    .map(|x| x.digits);
+                if let Some(digits) = digits {
+                    let readings = history(
+                        &transaction.find::<MetricValue>(metric_rows.clone())?,
+                        &transaction.find::<MetricReplacement>(metric_rows)?,
+                    );
+                    match check_value(value, digits, &readings) {
+                        ReadingCheck::Ok => {}
+                        ReadingCheck::TooLarge(_) => {
+                            let largest = (10u64.pow(digits) - 1) as u32;
+                            return Ok(Err(CommandError::ReadingTooLarge(largest)));
+                        }
+                        ReadingCheck::Rollover(last) if !rollover => {
+                            return Ok(Err(CommandError::ReadingTooSmall(last)))
+                        }
+                        ReadingCheck::Rollover(_) => {}
+                    }
+                }
+
+                transaction.insert(MetricValue::new(metric_id, value, &added))?;
+                Ok(Ok(transaction.undo_action_id()))
+            })
+            .await;
+        match result {
+            Ok(Ok(action_id)) => Ok((alert.into_iter().collect(), action_id)),
+            Ok(Err(error)) => Err(error),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Replaces the counters of the metrics of the meter, where every metric
+    /// gets the final value of the old counter and the initial one of the new.
+    /// The meter and its metrics stay the same, so do their readings.
+    pub async fn replace_meter(
+        &self,
+        values: Vec<(u32, u32, u32)>,
+        replaced: NaiveDateTime,
+    ) -> CommandResult<UndoActionId> {
+        match self
+            .ops
+            .insert_many(
+                values
+                    .into_iter()
+                    .map(|(metric_id, old_value, new_value)| {
+                        MetricReplacement::new(metric_id, old_value, new_value, &replaced)
+                    })
+                    .collect(),
+            )
+            .await
+        {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Replacements of the counter of the metric, the latest first.
+    pub async fn list_replacements(&self, metric_id: u32) -> CommandResult<Vec<MetricReplacement>> {
+        match self
+            .ops
+            .find::<MetricReplacement>(Where::new("metric_id", WhereExprOperator::Equal, metric_id))
+            .await
+        {
+            Ok(mut rows) => {
+                rows.sort_by_key(|x| std::cmp::Reverse(x.replaced));
+                Ok(rows)
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Readings of the metric together with the replacements of its counter.
+    async fn metric_history(&self, metric_id: u32) -> CommandResult<Vec<Reading>> {
+        let metric_rows = Where::new("metric_id", WhereExprOperator::Equal, metric_id);
+        match (
+            self.ops.find::<MetricValue>(metric_rows.clone()).await,
+            self.ops.find::<MetricReplacement>(metric_rows).await,
+        ) {
+            (Ok(values), Ok(replacements)) => Ok(history(&values, &replacements)),
+            (Err(error), _) | (_, Err(error)) => Err(error.into()),
+        }
+    }
+
+    /// Factor of the abnormal consumption alerts of the metric, if they are on.
+    pub async fn get_alert_factor(&self, metric_id: u32) -> CommandResult<Option<f64>> {
+        match self
+            .ops
+            .find::<MetricAlert>(Where::new("metric_id", WhereExprOperator::Equal, metric_id))
+            .await
+        {
+            Ok(rows) => Ok(match rows.into_iter().next() {
+                Some(alert) => alert.factor,
+                None => Some(DEFAULT_ALERT_FACTOR),
+            }),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Sets the factor of the alerts of the metric, no factor turns them off.
+    pub async fn set_alert_factor(
+        &self,
+        metric_id: u32,
+        factor: Option<f64>,
+    ) -> CommandResult<UndoActionId> {
+        if factor.is_some_and(|x| !x.is_finite() || x <= 1.0) {
+            return Err(CommandError::InvalidAlertFactor);
+        }
+
+        match self
+            .ops
+            .transaction(move |transaction| {
+                transaction.delete_where::<MetricAlert>(Where::new(
+                    "metric_id",
+                    WhereExprOperator::Equal,
+                    metric_id,
+                ))?;
+                transaction.insert(MetricAlert::new(metric_id, factor))?;
+                Ok(transaction.undo_action_id())
+            })
+            .await
+        {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Metrics of the meters in use that have no values submitted at `since`
+    /// or later.
+    pub async fn missing_readings(&self, since: NaiveDateTime) -> CommandResult<Vec<Metric>> {
+        let metrics = match (
+            self.ops.get_all::<Metric>().await,
+            self.ops.find::<Meter>(archived_meters(true)).await,
+        ) {
+            (Ok(metrics), Ok(archived)) => metrics
+                .into_iter()
+                .filter(|metric| !archived.iter().any(|x| x.id == metric.meter_id))
+                .collect::<Vec<Metric>>(),
+            (Err(error), _) | (_, Err(error)) => return Err(error.into()),
+        };
+        let values = match self
+            .ops
+            .find::<MetricValue>(Where::new(
+                "added",
+                WhereExprOperator::GreaterOrEqual,
+                since,
+            ))
+            .await
+        {
+            Ok(values) => values,
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(metrics
+            .into_iter()
+            .filter(|metric| !values.iter().any(|x| x.metric_id == metric.id))
+            .collect())
+    }
+
+    pub async fn set_reminder(
+        &self,
+        chat_id: i64,
+        first_day: u32,
+        last_day: u32,
+        hour: u32,
+        language: &str,
+    ) -> CommandResult<UndoActionId> {
+        if first_day < 1 || first_day > last_day || last_day > 31 || hour > 23 {
+            return Err(CommandError::InvalidReminder);
+        }
+
+        let reminder = Reminder::new(chat_id, first_day, last_day, hour, language);
+        match self
+            .ops
+            .transaction(move |transaction| {
+                transaction.delete_where::<Reminder>(Where::new(
+                    "chat_id",
+                    WhereExprOperator::Equal,
+                    chat_id,
+                ))?;
+                transaction.insert(reminder)?;
+                Ok(transaction.undo_action_id())
+            })
+            .await
+        {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
         }
     }
 
-    pub async fn delete_meter(&self, id: u32) -> CommandResultNoValue {
-        match self.ops.delete_by_id::<Meter>(id).await {
+    pub async fn get_reminder(&self, chat_id: i64) -> CommandResult<Option<Reminder>> {
+        match self
+            .ops
+            .find::<Reminder>(Where::new("chat_id", WhereExprOperator::Equal, chat_id))
+            .await
+        {
+            Ok(rows) => Ok(rows.into_iter().next()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn delete_reminder(&self, chat_id: i64) -> CommandResult<UndoActionId> {
+        match self
+            .ops
+            .transaction(move |transaction| {
+                transaction.delete_where::<Reminder>(Where::new(
+                    "chat_id",
+                    WhereExprOperator::Equal,
+                    chat_id,
+                ))?;
+                Ok(transaction.undo_action_id())
+            })
+            .await
+        {
+            Ok(action_id) => Ok(action_id),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Reminders that should be sent at `now`, each with the metrics that
+    /// still miss readings for the current period.
+    pub async fn due_reminders(
+        &self,
+        now: NaiveDateTime,
+    ) -> CommandResult<Vec<(Reminder, Vec<Metric>)>> {
+        let reminders = match self.ops.get_all::<Reminder>().await {
+            Ok(reminders) => reminders,
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut due = Vec::new();
+        for reminder in reminders {
+            if let Some(period_start) = reminder_period_start(&reminder, now) {
+                let missing = self.missing_readings(period_start).await?;
+                if !missing.is_empty() {
+                    due.push((reminder, missing));
+                }
+            }
+        }
+        Ok(due)
+    }
+
+    pub async fn mark_reminder_sent(
+        &self,
+        mut reminder: Reminder,
+        date: NaiveDate,
+    ) -> CommandResultNoValue {
+        reminder.last_sent = Some(date);
+        match self
+            .ops
+            .bookkeeping(move |transaction| transaction.update(reminder))
+            .await
+        {
             Ok(_) => Ok(()),
-            Err(error) => Err(error.to_string()),
+            Err(error) => Err(error.into()),
         }
     }
 
-    pub async fn list_meters(&self) -> CommandResult<Vec<Meter>> {
-        match self.ops.get_all::<Meter>().await {
+    pub async fn get_user_language(&self, user_id: i64) -> CommandResult<Option<String>> {
+        match self
+            .ops
+            .find::<UserSettings>(Where::new("user_id", WhereExprOperator::Equal, user_id))
+            .await
+        {
+            Ok(rows) => Ok(rows.into_iter().next().map(|x| x.language)),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn set_user_language(&self, user_id: i64, language: &str) -> CommandResultNoValue {
+        let language = language.to_string();
+        match self
+            .ops
+            .bookkeeping(move |transaction| {
+                match transaction
+                    .find::<UserSettings>(Where::new("user_id", WhereExprOperator::Equal, user_id))?
+                    .into_iter()
+                    .next()
+                {
+                    Some(mut settings) => {
+                        settings.language = language;
+                        transaction.update(settings)
+                    }
+                    None => {
+                        transaction.insert(UserSettings::new(user_id, &language))?;
+                        Ok(())
+                    }
+                }
+            })
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Readings submitted from `from` to `to` inclusive, oldest first, as CSV
+    /// with a row per value. Missing bounds don't limit the range.
+    pub async fn export_readings(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> CommandResult<String> {
+        let mut conditions = Vec::new();
+        if let Some(from) = from.and_then(|x| x.and_hms_opt(0, 0, 0)) {
+            conditions.push(("added", WhereExprOperator::GreaterOrEqual, from));
+        }
+        if let Some(to) = to
+            .and_then(|x| x.succ_opt())
+            .and_then(|x| x.and_hms_opt(0, 0, 0))
+        {
+            conditions.push(("added", WhereExprOperator::Less, to));
+        }
+        let condition = conditions.into_iter().fold(
+            None,
+            |condition: Option<Where>, (field, operator, value)| {
+                Some(match condition {
+                    Some(condition) => condition.and(field, operator, value),
+                    None => Where::new(field, operator, value),
+                })
+            },
+        );
+
+        let values = match condition {
+            Some(condition) => self.ops.find::<MetricValue>(condition).await,
+            None => self.ops.get_all::<MetricValue>().await,
+        };
+        let (mut values, metrics, meters) = match (
+            values,
+            self.ops.get_all::<Metric>().await,
+            self.ops.get_all::<Meter>().await,
+        ) {
+            (Ok(values), Ok(metrics), Ok(meters)) => (values, metrics, meters),
+            (Err(error), _, _) | (_, Err(error), _) | (_, _, Err(error)) => {
+                return Err(error.into())
+            }
+        };
+        values.sort_by_key(|x| (x.added, x.metric_id));
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.write_record(CSV_HEADER)?;
+        for value in values {
+            let metric = match metrics.iter().find(|x| x.id == value.metric_id) {
+                Some(metric) => metric,
+                None => continue,
+            };
+            let meter = match meters.iter().find(|x| x.id == metric.meter_id) {
+                Some(meter) => meter,
+                None => continue,
+            };
+            writer.write_record([
+                meter.name.clone(),
+                metric.name.clone(),
+                value.value.to_string(),
+                metric.rate.to_string(),
+                value.added.format(CSV_TIMESTAMP_FORMAT).to_string(),
+            ])?;
+        }
+
+        match writer.into_inner() {
+            Ok(bytes) => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+            Err(error) => Err(CommandError::Csv(error.to_string())),
+        }
+    }
+
+    /// Parses and validates the CSV file against the existing meters and metrics
+    /// without changing anything.
+    pub async fn plan_import(&self, csv: Vec<u8>) -> CommandResult<ImportPlan> {
+        let (rows, errors) = parse_import(&csv);
+        let (meters, metrics, values) = match (
+            self.ops.get_all::<Meter>().await,
+            self.ops.get_all::<Metric>().await,
+            self.ops.get_all::<MetricValue>().await,
+        ) {
+            (Ok(meters), Ok(metrics), Ok(values)) => (meters, metrics, values),
+            (Err(error), _, _) | (_, Err(error), _) | (_, _, Err(error)) => {
+                return Err(error.into())
+            }
+        };
+
+        let mut plan = ImportPlan {
+            errors,
+            ..Default::default()
+        };
+        for row in &rows {
+            let meter = meters.iter().find(|x| x.name == row.meter);
+            if meter.is_none() && !plan.missing_meters.contains(&row.meter) {
+                plan.missing_meters.push(row.meter.clone());
+            }
+            let metric = meter.and_then(|meter| {
+                metrics
+                    .iter()
+                    .find(|x| x.meter_id == meter.id && x.name == row.metric)
+            });
+            match metric {
+                Some(metric) => {
+                    if values
+                        .iter()
+                        .any(|x| x.metric_id == metric.id && x.added == row.added)
+                    {
+                        plan.duplicates += 1;
+                    }
+                }
+                None => {
+                    let names = (row.meter.clone(), row.metric.clone());
+                    if !plan.missing_metrics.contains(&names) {
+                        plan.missing_metrics.push(names);
+                    }
+                }
+            }
+        }
+        plan.rows = rows;
+        Ok(plan)
+    }
+
+    /// Inserts the rows in one transaction, skipping the ones that duplicate
+    /// existing values. Rows of missing meters and metrics are skipped too,
+    /// unless `create_missing` is set. Returns the number of inserted values
+    /// and the action undoing the import.
+    pub async fn import_readings(
+        &self,
+        rows: Vec<ImportRow>,
+        create_missing: bool,
+    ) -> CommandResult<(usize, UndoActionId)> {
+        match self
+            .ops
+            .transaction(move |transaction| {
+                let mut meters = transaction.get_all::<Meter>()?;
+                let mut metrics = transaction.get_all::<Metric>()?;
+                let mut existing = transaction
+                    .get_all::<MetricValue>()?
+                    .into_iter()
+                    .map(|x| (x.metric_id, x.added))
+                    .collect::<HashSet<(u32, NaiveDateTime)>>();
+
+                let mut values = Vec::new();
+                for row in rows {
+                    let meter_id = match meters.iter().find(|x| x.name == row.meter) {
+                        Some(meter) => meter.id,
+                        None if create_missing => {
+                            let mut meter = Meter::new(&row.meter);
+                            meter.id = transaction.insert(meter.clone())?;
+                            meters.push(meter);
+                            meters[meters.len() - 1].id
+                        }
+                        None => continue,
+                    };
+                    let metric_id = match metrics
+                        .iter()
+                        .find(|x| x.meter_id == meter_id && x.name == row.metric)
+                    {
+                        Some(metric) => metric.id,
+                        None if create_missing => {
+                            let mut metric = Metric::new(&row.metric, meter_id, row.rate);
+                            metric.id = transaction.insert(metric.clone())?;
+                            metrics.push(metric);
+                            metrics[metrics.len() - 1].id
+                        }
+                        None => continue,
+                    };
+                    if existing.insert((metric_id, row.added)) {
+                        values.push(MetricValue::new(metric_id, row.value, &row.added));
+                    }
+                }
+
+                let count = values.len();
+                transaction.insert_many(values)?;
+                Ok((count, transaction.undo_action_id()))
+            })
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Consumption of every metric of the meter by month, for the months
+    /// starting from `since` or for all of them.
+    pub async fn monthly_consumption(
+        &self,
+        meter_id: u32,
+        since: Option<NaiveDate>,
+    ) -> CommandResult<Vec<(Metric, Vec<(NaiveDate, u32)>)>> {
+        let metrics = self.list_metrics(meter_id).await?;
+
+        let mut result = Vec::new();
+        for metric in metrics {
+            let readings = self.metric_history(metric.id).await?;
+            let digits = self.get_digits(metric.id).await?;
+
+            let months = consumption_by_month(&readings, digits)
+                .into_iter()
+                .filter(|(month, _)| since.is_none_or(|since| *month >= since))
+                .collect();
+            result.push((metric, months));
+        }
+        Ok(result)
+    }
+
+    /// Statement of the month of `month`: a line for every metric of the meters
+    /// in use having readings in it, with the cost of the consumption at the
+    /// metric rate.
+    pub async fn monthly_report(&self, month: NaiveDate) -> CommandResult<Report> {
+        let month = month_start(month);
+        let previous_month = month.checked_sub_months(Months::new(1)).unwrap_or(month);
+        let (meters, metrics, values, capacities, replacements) = match (
+            self.ops.find::<Meter>(archived_meters(false)).await,
+            self.ops.get_all::<Metric>().await,
+            self.ops.get_all::<MetricValue>().await,
+            self.ops.get_all::<MetricCapacity>().await,
+            self.ops.get_all::<MetricReplacement>().await,
+        ) {
+            (Ok(meters), Ok(metrics), Ok(values), Ok(capacities), Ok(replacements)) => {
+                (meters, metrics, values, capacities, replacements)
+            }
+            (Err(error), _, _, _, _)
+            | (_, Err(error), _, _, _)
+            | (_, _, Err(error), _, _)
+            | (_, _, _, Err(error), _)
+            | (_, _, _, _, Err(error)) => return Err(error.into()),
+        };
+
+        let mut lines = Vec::new();
+        let mut previous_total = None;
+        for meter in &meters {
+            for metric in metrics.iter().filter(|x| x.meter_id == meter.id) {
+                let readings = history(
+                    &values
+                        .iter()
+                        .filter(|x| x.metric_id == metric.id)
+                        .cloned()
+                        .collect::<Vec<MetricValue>>(),
+                    &replacements
+                        .iter()
+                        .filter(|x| x.metric_id == metric.id)
+                        .cloned()
+                        .collect::<Vec<MetricReplacement>>(),
+                );
+                let digits = capacities
+                    .iter()
+                    .find(|x| x.metric_id == metric.id)
+                    .map(|x| x.digits);
+
+                let previous = month_readings(&readings, previous_month, digits);
+                if let Some((_, _, consumption)) = previous {
+                    *previous_total.get_or_insert(0) += consumption as u64 * metric.rate as u64;
+                }
+                if let Some((start, end, consumption)) = month_readings(&readings, month, digits) {
+                    lines.push(ReportLine {
+                        meter: meter.name.clone(),
+                        metric: metric.name.clone(),
+                        start,
+                        end,
+                        consumption,
+                        rate: metric.rate,
+                        cost: consumption as u64 * metric.rate as u64,
+                        previous_consumption: previous.map(|x| x.2),
+                    });
+                }
+            }
+        }
+
+        Ok(Report {
+            month,
+            total: lines.iter().map(|x| x.cost).sum(),
+            lines,
+            previous_total,
+        })
+    }
+
+    /// Returns every metric of the meter with its last submitted value.
+    pub async fn latest_readings(
+        &self,
+        meter_id: u32,
+    ) -> CommandResult<Vec<(Metric, Option<MetricValue>)>> {
+        match self.ops.get_latest_values(meter_id).await {
             Ok(rows) => Ok(rows),
-            Err(error) => Err(error.to_string()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+/// Reads the rows of a CSV file, collecting the numbers of the lines that
+/// can't be read together with the reason.
+fn parse_import(csv: &[u8]) -> (Vec<ImportRow>, Vec<(u64, ImportError)>) {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(csv);
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, record) in reader.records().enumerate() {
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                let line = error.position().map_or(index as u64 + 1, |x| x.line());
+                errors.push((line, ImportError::Format));
+                continue;
+            }
+        };
+        let line = record.position().map_or(index as u64 + 1, |x| x.line());
+        if index == 0 {
+            if record.iter().ne(CSV_HEADER) {
+                errors.push((line, ImportError::Header));
+            }
+            continue;
+        }
+
+        let (meter, metric, value, rate, added) = match record.iter().collect::<Vec<&str>>()[..] {
+            [meter, metric, value, rate, added] => (meter, metric, value, rate, added),
+            _ => {
+                errors.push((line, ImportError::Format));
+                continue;
+            }
+        };
+        let added = NaiveDateTime::parse_from_str(added, CSV_TIMESTAMP_FORMAT).or_else(|_| {
+            NaiveDate::parse_from_str(added, CSV_DATE_FORMAT)
+                .map(|x| x.and_hms_opt(0, 0, 0).unwrap_or_default())
+        });
+        match (value.parse::<u32>(), rate.parse::<u32>(), added) {
+            _ if meter.is_empty() || metric.is_empty() => {
+                errors.push((line, ImportError::EmptyName))
+            }
+            (Err(_), _, _) => errors.push((line, ImportError::Value)),
+            (_, Err(_), _) => errors.push((line, ImportError::Rate)),
+            (_, _, Err(_)) => errors.push((line, ImportError::Timestamp)),
+            (Ok(value), Ok(rate), Ok(added)) => rows.push(ImportRow {
+                line,
+                meter: meter.to_string(),
+                metric: metric.to_string(),
+                value,
+                rate,
+                added,
+            }),
+        }
+    }
+    (rows, errors)
+}
+
+/// Consumption between two readings of a counter with `digits` digits, which
+/// starts from zero again after the largest number it can show.
+pub fn consumption(from: u32, to: u32, digits: Option<u32>) -> u32 {
+    match digits.map(|x| 10u64.pow(x)) {
+        Some(capacity) if to < from && (from as u64) < capacity => {
+            (capacity - from as u64 + to as u64) as u32
+        }
+        _ => to.saturating_sub(from),
+    }
+}
+
+/// A value in the history of a metric: a submitted reading, or one of the two
+/// values of a replacement of its counter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Reading {
+    /// Id of the submitted value, replacements have none.
+    id: Option<u32>,
+    added: NaiveDateTime,
+    value: u32,
+    /// Whether it is the initial value of a new counter, so nothing is
+    /// consumed since the previous value, which is of the old counter.
+    initial: bool,
+}
+
+/// See [`Commands::check_reading`], for a counter with `digits` and the
+/// `readings` of its metric.
+fn check_value(value: u32, digits: u32, readings: &[Reading]) -> ReadingCheck {
+    if value as u64 >= 10u64.pow(digits) {
+        return ReadingCheck::TooLarge(digits);
+    }
+
+    // after a replacement the last value is the initial one of the new counter
+    match readings.last() {
+        Some(last) if value < last.value => ReadingCheck::Rollover(last.value),
+        _ => ReadingCheck::Ok,
+    }
+}
+
+/// Readings of the metric mixed with its replacements, sorted by date. Values
+/// submitted at the time of a replacement are taken as of the old counter.
+fn history(values: &[MetricValue], replacements: &[MetricReplacement]) -> Vec<Reading> {
+    let mut readings = values
+        .iter()
+        .map(|x| Reading {
+            id: Some(x.id),
+            added: x.added,
+            value: x.value,
+            initial: false,
+        })
+        .collect::<Vec<Reading>>();
+    for replacement in replacements {
+        readings.push(Reading {
+            id: None,
+            added: replacement.replaced,
+            value: replacement.old_value,
+            initial: false,
+        });
+        readings.push(Reading {
+            id: None,
+            added: replacement.replaced,
+            value: replacement.new_value,
+            initial: true,
+        });
+    }
+    // the sort is stable, so a replacement stays after the values of its time
+    readings.sort_by_key(|x| x.added);
+    readings
+}
+
+/// Consumption between two consecutive readings of the history.
+fn step_consumption(from: &Reading, to: &Reading, digits: Option<u32>) -> u32 {
+    match to.initial {
+        true => 0,
+        false => consumption(from.value, to.value, digits),
+    }
+}
+
+/// Consumption over the consecutive `readings`, counting every rollover and
+/// every replacement.
+fn total_consumption(readings: &[Reading], digits: Option<u32>) -> u32 {
+    readings
+        .windows(2)
+        .map(|x| step_consumption(&x[0], &x[1], digits))
+        .fold(0, u32::saturating_add)
+}
+
+/// Daily consumption from the last of `readings` before `added` to `value`, and
+/// the average daily consumption in the [`RECENT_DAYS`] before that last one.
+/// `readings` must be sorted by date.
+fn daily_consumption(
+    readings: &[Reading],
+    value: u32,
+    added: NaiveDateTime,
+    digits: Option<u32>,
+) -> Option<(f64, f64)> {
+    let days = |from: NaiveDateTime, to: NaiveDateTime| (to - from).num_minutes() as f64 / 1440.0;
+
+    let previous = readings.iter().rev().find(|x| x.added < added)?;
+    let recent = readings
+        .iter()
+        .filter(|x| {
+            x.added >= previous.added - Duration::days(RECENT_DAYS) && x.added <= previous.added
+        })
+        .copied()
+        .collect::<Vec<Reading>>();
+    let (days, recent_days) = (
+        days(previous.added, added),
+        days(recent.first()?.added, previous.added),
+    );
+    if days <= 0.0 || recent_days <= 0.0 {
+        return None;
+    }
+    Some((
+        consumption(previous.value, value, digits) as f64 / days,
+        total_consumption(&recent, digits) as f64 / recent_days,
+    ))
+}
+
+/// Condition selecting either the archived meters or the ones in use.
+fn archived_meters(archived: bool) -> Where {
+    Where::new("archived", WhereExprOperator::Equal, archived as u32)
+}
+
+/// First day of the month of `date`.
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+/// Consumption in every month having readings, from the last reading before
+/// the month, or the first one in it, to the last reading in it. `readings`
+/// must be sorted by date.
+fn consumption_by_month(readings: &[Reading], digits: Option<u32>) -> Vec<(NaiveDate, u32)> {
+    let mut result: Vec<(NaiveDate, u32)> = Vec::new();
+    let mut previous: Option<&Reading> = None;
+    for reading in readings {
+        let month = month_start(reading.added.date());
+        if result.last().map(|x| x.0) != Some(month) {
+            result.push((month, 0));
         }
+        if let (Some(last), Some(previous)) = (result.last_mut(), previous) {
+            last.1 = last
+                .1
+                .saturating_add(step_consumption(previous, reading, digits));
+        }
+        previous = Some(reading);
+    }
+    result
+}
+
+/// First and last readings of the month starting at `month`, where the first
+/// one is the last before the month if there is such, and the consumption
+/// between them. `readings` must be sorted by date.
+fn month_readings(
+    readings: &[Reading],
+    month: NaiveDate,
+    digits: Option<u32>,
+) -> Option<(u32, u32, u32)> {
+    let in_month = readings
+        .iter()
+        .filter(|x| month_start(x.added.date()) == month)
+        .copied()
+        .collect::<Vec<Reading>>();
+    if in_month.is_empty() {
+        return None;
+    }
+    let mut month_readings = readings
+        .iter()
+        .rev()
+        .find(|x| x.added.date() < month)
+        .map(|x| vec![*x])
+        .unwrap_or_default();
+    month_readings.extend(in_month);
+    Some((
+        month_readings.first()?.value,
+        month_readings.last()?.value,
+        total_consumption(&month_readings, digits),
+    ))
+}
+
+/// Number of days in the month of `date`.
+fn days_in_month(date: NaiveDate) -> u32 {
+    month_start(date)
+        .checked_add_months(Months::new(1))
+        .and_then(|x| x.pred_opt())
+        .map_or(31, |x| x.day())
+}
+
+/// Start of the reminder period containing `now`, if the reminder should be
+/// sent at `now` and hasn't been sent today yet. Days past the end of a short
+/// month mean its last day, so that the reminder is sent every month.
+fn reminder_period_start(reminder: &Reminder, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let today = now.date();
+    let days = days_in_month(today);
+    let first_day = reminder.first_day.min(days);
+    let last_day = reminder.last_day.min(days);
+    if today.day() < first_day
+        || today.day() > last_day
+        || now.hour() < reminder.hour
+        || reminder.last_sent == Some(today)
+    {
+        return None;
     }
+    today.with_day(first_day)?.and_hms_opt(0, 0, 0)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::commands::Commands;
+    use crate::commands::{
+        consumption, total_consumption, CommandError, Commands, ImportError, Reading, ReadingCheck,
+        ReportLine, DEFAULT_ALERT_FACTOR, MAX_DIGITS, UNDO_MINUTES,
+    };
     use crate::database::create::create_tables_if_do_not_exist;
+    use crate::database::structs::{Meter, Metric, MetricValue, ReadingPhoto};
+    use chrono::{Duration, Local, NaiveDate, NaiveDateTime};
 
     #[tokio::test]
     async fn test_meter_commands() {
@@ -69,7 +1639,10 @@ mod test {
         assert!(commands.add_meter("meter1").await.is_ok());
         assert!(commands.add_meter("meter2").await.is_ok());
         // same name again, should fail
-        assert!(commands.add_meter("meter1").await.is_err());
+        assert_eq!(
+            commands.add_meter("meter1").await,
+            Err(CommandError::MeterExists("meter1".to_string()))
+        );
 
         let mut meters = commands.list_meters().await.unwrap();
         assert_eq!(meters.len(), 2);
@@ -82,4 +1655,1331 @@ mod test {
         meters = commands.list_meters().await.unwrap();
         assert_eq!(meters.len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_archive_meters() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("house").await.is_ok());
+        assert!(commands.add_meter("flat").await.is_ok());
+        let meters = commands.list_meters().await.unwrap();
+        for meter in &meters {
+            commands
+                .ops
+                .insert(Metric::new("power", meter.id, 100))
+                .await
+                .unwrap();
+        }
+        let date = NaiveDate::from_ymd_opt(2023, 9, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        assert_eq!(commands.missing_readings(date).await.unwrap().len(), 2);
+
+        assert!(commands.archive_meter(meters[0].id).await.is_ok());
+        let active = commands.list_meters().await.unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].name, "flat");
+        assert_eq!(commands.list_meters_page(0, 10).await.unwrap().total, 1);
+        let archived = commands.list_archived_meters_page(0, 10).await.unwrap();
+        assert_eq!(archived.total, 1);
+        assert_eq!(archived.items[0].name, "house");
+        assert!(archived.items[0].archived);
+        let missing = commands.missing_readings(date).await.unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].meter_id, meters[1].id);
+        // archived meters keep their names
+        assert_eq!(
+            commands.add_meter("house").await,
+            Err(CommandError::MeterExists("house".to_string()))
+        );
+
+        assert!(commands.restore_meter(meters[0].id).await.is_ok());
+        assert_eq!(commands.list_meters().await.unwrap().len(), 2);
+        assert_eq!(
+            commands
+                .list_archived_meters_page(0, 10)
+                .await
+                .unwrap()
+                .total,
+            0
+        );
+        assert_eq!(
+            commands.archive_meter(100).await,
+            Err(CommandError::MeterNotFound(100))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_meter_with_metrics() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("meter1").await.is_ok());
+        assert!(commands.add_meter("meter2").await.is_ok());
+        let meters = commands.list_meters().await.unwrap();
+
+        let now = Local::now().naive_local();
+        for meter in &meters {
+            commands
+                .ops
+                .insert(Metric::new("metric", meter.id, 100))
+                .await
+                .unwrap();
+            let metric = commands.ops.get_last::<Metric>().await.unwrap().unwrap();
+            commands
+                .ops
+                .insert(MetricValue::new(metric.id, 123, &now))
+                .await
+                .unwrap();
+        }
+
+        assert!(commands.delete_meter(meters[0].id).await.is_ok());
+
+        assert_eq!(commands.ops.get_all::<Meter>().await.unwrap().len(), 1);
+        let metrics = commands.ops.get_all::<Metric>().await.unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].meter_id, meters[1].id);
+        let values = commands.ops.get_all::<MetricValue>().await.unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].metric_id, metrics[0].id);
+    }
+
+    #[tokio::test]
+    async fn test_latest_readings() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert_eq!(
+            commands.get_meter(1).await.err(),
+            Some(CommandError::MeterNotFound(1))
+        );
+        assert!(commands.add_meter("meter1").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        assert_eq!(commands.get_meter(meter.id).await.unwrap().name, "meter1");
+
+        commands
+            .ops
+            .insert_many(vec![
+                Metric::new("day", meter.id, 100),
+                Metric::new("night", meter.id, 50),
+            ])
+            .await
+            .unwrap();
+        let metrics = commands.ops.get_all::<Metric>().await.unwrap();
+        let now = Local::now().naive_local();
+        commands
+            .ops
+            .insert(MetricValue::new(metrics[0].id, 123, &now))
+            .await
+            .unwrap();
+
+        let readings = commands.latest_readings(meter.id).await.unwrap();
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].0.name, "day");
+        assert_eq!(readings[0].1.as_ref().unwrap().value, 123);
+        assert_eq!(readings[1].0.name, "night");
+        assert!(readings[1].1.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pages() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        for index in 1..=12 {
+            assert!(commands
+                .add_meter(&std::format!("meter{}", index))
+                .await
+                .is_ok());
+        }
+
+        let page = commands.list_meters_page(10, 5).await.unwrap();
+        assert_eq!(page.total, 12);
+        assert_eq!(page.offset, 10);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.items[0].name, "meter11");
+
+        let meter = page.items[0].clone();
+        commands
+            .ops
+            .insert_many(vec![
+                Metric::new("day", meter.id, 100),
+                Metric::new("night", meter.id, 50),
+            ])
+            .await
+            .unwrap();
+        let page = commands.list_metrics_page(meter.id, 0, 5).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.items[1].name, "night");
+        assert_eq!(
+            commands.get_metric(page.items[1].id).await.unwrap().name,
+            "night"
+        );
+
+        let metric = page.items[0].clone();
+        let date = |day| {
+            NaiveDate::from_ymd_opt(2023, 12, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
+        commands
+            .ops
+            .insert_many(vec![
+                MetricValue::new(metric.id, 300, &date(3)),
+                MetricValue::new(metric.id, 100, &date(1)),
+                MetricValue::new(metric.id, 200, &date(2)),
+            ])
+            .await
+            .unwrap();
+        let page = commands.list_readings_page(metric.id, 0, 2).await.unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(
+            page.items.iter().map(|x| x.value).collect::<Vec<u32>>(),
+            vec![300, 200]
+        );
+
+        // values imported at once share the timestamp, but not the pages
+        commands
+            .ops
+            .insert_many(
+                (1..=5)
+                    .map(|x| MetricValue::new(metric.id, 400 + x, &date(4)))
+                    .collect(),
+            )
+            .await
+            .unwrap();
+        let mut values = Vec::new();
+        for offset in (0..8).step_by(2) {
+            let page = commands
+                .list_readings_page(metric.id, offset, 2)
+                .await
+                .unwrap();
+            values.extend(page.items.iter().map(|x| x.value));
+        }
+        assert_eq!(values, vec![405, 404, 403, 402, 401, 300, 200, 100]);
+    }
+
+    #[tokio::test]
+    async fn test_submit_and_missing_readings() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("meter1").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert_many(vec![
+                Metric::new("day", meter.id, 100),
+                Metric::new("night", meter.id, 50),
+            ])
+            .await
+            .unwrap();
+        let metrics = commands.list_metrics(meter.id).await.unwrap();
+        assert_eq!(metrics.len(), 2);
+
+        let date = |day| {
+            NaiveDate::from_ymd_opt(2023, 12, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        assert!(commands
+            .submit_readings(vec![(metrics[0].id, 10), (metrics[1].id, 20)], date(1))
+            .await
+            .is_ok());
+        assert_eq!(commands.missing_readings(date(15)).await.unwrap().len(), 2);
+
+        assert!(commands
+            .submit_readings(vec![(metrics[1].id, 30)], date(16))
+            .await
+            .is_ok());
+        let missing = commands.missing_readings(date(15)).await.unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].name, "day");
+    }
+
+    #[tokio::test]
+    async fn test_export_readings() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("electricity").await.is_ok());
+        assert!(commands.add_meter("water, cold").await.is_ok());
+        let meters = commands.list_meters().await.unwrap();
+        commands
+            .ops
+            .insert_many(vec![
+                Metric::new("day", meters[0].id, 100),
+                Metric::new("volume", meters[1].id, 30),
+            ])
+            .await
+            .unwrap();
+        let metrics = commands.ops.get_all::<Metric>().await.unwrap();
+
+        let date = |month, day| NaiveDate::from_ymd_opt(2023, month, day).unwrap();
+        for (month, value) in [(10, 1), (11, 2), (12, 3)] {
+            assert!(commands
+                .submit_readings(
+                    vec![(metrics[0].id, value * 10), (metrics[1].id, value)],
+                    date(month, 1).and_hms_opt(12, 30, 0).unwrap()
+                )
+                .await
+                .is_ok());
+        }
+
+        let header = "meter,metric,value,rate,timestamp\n";
+        assert_eq!(
+            commands
+                .export_readings(Some(date(11, 1)), Some(date(11, 30)))
+                .await
+                .unwrap(),
+            std::format!(
+                "{}{}{}",
+                header,
+                "electricity,day,20,100,2023-11-01 12:30:00\n",
+                "\"water, cold\",volume,2,30,2023-11-01 12:30:00\n"
+            )
+        );
+        // the last day is included
+        assert_eq!(
+            commands
+                .export_readings(None, Some(date(11, 1)))
+                .await
+                .unwrap()
+                .lines()
+                .count(),
+            5
+        );
+        assert_eq!(
+            commands
+                .export_readings(Some(date(12, 2)), None)
+                .await
+                .unwrap(),
+            header
+        );
+        assert_eq!(
+            commands
+                .export_readings(None, None)
+                .await
+                .unwrap()
+                .lines()
+                .count(),
+            7
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_readings() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("electricity").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert(Metric::new("day", meter.id, 100))
+            .await
+            .unwrap();
+        let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+        let added = NaiveDate::from_ymd_opt(2023, 11, 1)
+            .unwrap()
+            .and_hms_opt(12, 30, 0)
+            .unwrap();
+        assert!(commands
+            .submit_readings(vec![(metric.id, 20)], added)
+            .await
+            .is_ok());
+
+        let csv = [
+            "meter,metric,value,rate,timestamp",
+            "electricity,day,20,100,2023-11-01 12:30:00",
+            "electricity,day,30,100,2023-12-01",
+            "electricity,night,10,50,2023-12-01",
+            "\"water, cold\",volume,5,30,2023-12-01 10:00:00",
+            "electricity,day,-1,100,2023-12-01",
+            "electricity,day,1,x,2023-12-01",
+            "electricity,day,1,100,yesterday",
+            ",day,1,100,2023-12-01",
+            "electricity,day,1",
+        ]
+        .join("\n");
+        let plan = commands.plan_import(csv.into_bytes()).await.unwrap();
+        assert_eq!(
+            plan.rows.iter().map(|x| x.line).collect::<Vec<u64>>(),
+            vec![2, 3, 4, 5]
+        );
+        assert_eq!(plan.rows[1].value, 30);
+        assert_eq!(plan.rows[3].meter, "water, cold");
+        assert_eq!(
+            plan.errors,
+            vec![
+                (6, ImportError::Value),
+                (7, ImportError::Rate),
+                (8, ImportError::Timestamp),
+                (9, ImportError::EmptyName),
+                (10, ImportError::Format),
+            ]
+        );
+        assert_eq!(plan.missing_meters, vec!["water, cold".to_string()]);
+        assert_eq!(
+            plan.missing_metrics,
+            vec![
+                ("electricity".to_string(), "night".to_string()),
+                ("water, cold".to_string(), "volume".to_string()),
+            ]
+        );
+        assert_eq!(plan.duplicates, 1);
+
+        // only the new value of the existing metric
+        assert_eq!(
+            commands
+                .import_readings(plan.rows.clone(), false)
+                .await
+                .unwrap()
+                .0,
+            1
+        );
+        assert_eq!(commands.list_meters().await.unwrap().len(), 1);
+        assert_eq!(
+            commands.import_readings(plan.rows, true).await.unwrap().0,
+            2
+        );
+        assert_eq!(commands.list_meters().await.unwrap().len(), 2);
+        assert_eq!(commands.ops.get_all::<Metric>().await.unwrap().len(), 3);
+        assert_eq!(commands.ops.count::<MetricValue>().await.unwrap(), 4);
+
+        let plan = commands
+            .plan_import(b"name,value\n".to_vec())
+            .await
+            .unwrap();
+        assert!(plan.rows.is_empty());
+        assert_eq!(plan.errors, vec![(1, ImportError::Header)]);
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("meter1").await.is_ok());
+        let backup = commands.backup().await.unwrap();
+        assert!(backup.starts_with(b"SQLite format 3"));
+
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        assert!(commands.delete_meter(meter.id).await.is_ok());
+        assert!(commands.add_meter("meter2").await.is_ok());
+
+        assert!(commands.restore(backup).await.is_ok());
+        let meters = commands.list_meters().await.unwrap();
+        assert_eq!(meters.len(), 1);
+        assert_eq!(meters[0].name, "meter1");
+
+        assert_eq!(
+            commands.restore(b"meter,metric".to_vec()).await,
+            Err(CommandError::InvalidBackup)
+        );
+        assert_eq!(commands.list_meters().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_monthly_consumption() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("meter1").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert_many(vec![
+                Metric::new("day", meter.id, 100),
+                Metric::new("night", meter.id, 50),
+            ])
+            .await
+            .unwrap();
+        let metrics = commands.list_metrics(meter.id).await.unwrap();
+
+        let date = |month, day| {
+            NaiveDate::from_ymd_opt(2023, month, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        for (month, day, value) in [(9, 20, 100), (9, 30, 110), (10, 25, 150), (12, 1, 200)] {
+            assert!(commands
+                .submit_readings(vec![(metrics[0].id, value)], date(month, day))
+                .await
+                .is_ok());
+        }
+
+        let month = |month| NaiveDate::from_ymd_opt(2023, month, 1).unwrap();
+        let consumption = commands.monthly_consumption(meter.id, None).await.unwrap();
+        assert_eq!(consumption.len(), 2);
+        assert_eq!(consumption[0].0.name, "day");
+        assert_eq!(
+            consumption[0].1,
+            vec![(month(9), 10), (month(10), 40), (month(12), 50)]
+        );
+        assert!(consumption[1].1.is_empty());
+
+        let consumption = commands
+            .monthly_consumption(meter.id, Some(month(10)))
+            .await
+            .unwrap();
+        assert_eq!(consumption[0].1, vec![(month(10), 40), (month(12), 50)]);
+    }
+
+    #[tokio::test]
+    async fn test_monthly_report() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("electricity").await.is_ok());
+        assert!(commands.add_meter("water").await.is_ok());
+        let meters = commands.list_meters().await.unwrap();
+        commands
+            .ops
+            .insert_many(vec![
+                Metric::new("day", meters[0].id, 5),
+                Metric::new("night", meters[0].id, 2),
+                Metric::new("cold", meters[1].id, 30),
+            ])
+            .await
+            .unwrap();
+        let metrics = commands.ops.get_all::<Metric>().await.unwrap();
+
+        let date = |month, day| {
+            NaiveDate::from_ymd_opt(2023, month, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        for (readings, added) in [
+            (
+                vec![(metrics[0].id, 100), (metrics[2].id, 10)],
+                date(10, 20),
+            ),
+            (
+                vec![(metrics[0].id, 150), (metrics[1].id, 40)],
+                date(11, 20),
+            ),
+            (vec![(metrics[1].id, 50)], date(11, 25)),
+            (
+                vec![(metrics[0].id, 180), (metrics[1].id, 60)],
+                date(12, 20),
+            ),
+        ] {
+            assert!(commands.submit_readings(readings, added).await.is_ok());
+        }
+
+        let report = commands.monthly_report(date(12, 31).date()).await.unwrap();
+        assert_eq!(report.month, NaiveDate::from_ymd_opt(2023, 12, 1).unwrap());
+        assert_eq!(
+            report.lines,
+            vec![
+                ReportLine {
+                    meter: "electricity".to_string(),
+                    metric: "day".to_string(),
+                    start: 150,
+                    end: 180,
+                    consumption: 30,
+                    rate: 5,
+                    cost: 150,
+                    previous_consumption: Some(50),
+                },
+                ReportLine {
+                    meter: "electricity".to_string(),
+                    metric: "night".to_string(),
+                    start: 50,
+                    end: 60,
+                    consumption: 10,
+                    rate: 2,
+                    cost: 20,
+                    previous_consumption: Some(10),
+                },
+            ]
+        );
+        assert_eq!(report.total, 170);
+        // 50 * 5 + 10 * 2
+        assert_eq!(report.previous_total, Some(270));
+
+        let report = commands.monthly_report(date(10, 1).date()).await.unwrap();
+        assert_eq!(report.lines.len(), 2);
+        assert_eq!(report.lines[1].consumption, 0);
+        assert_eq!(report.previous_total, None);
+
+        let report = commands.monthly_report(date(9, 1).date()).await.unwrap();
+        assert!(report.lines.is_empty());
+
+        // archived meters are left out, like everywhere else
+        assert!(commands.archive_meter(meters[0].id).await.is_ok());
+        let report = commands.monthly_report(date(12, 31).date()).await.unwrap();
+        assert!(report.lines.is_empty());
+        assert_eq!(report.total, 0);
+        assert_eq!(report.previous_total, None);
+        let report = commands.monthly_report(date(10, 1).date()).await.unwrap();
+        assert_eq!(report.lines.len(), 1);
+        assert_eq!(report.lines[0].meter, "water");
+    }
+
+    #[tokio::test]
+    async fn test_consumption_alerts() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("water").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert(Metric::new("cold", meter.id, 30))
+            .await
+            .unwrap();
+        let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+        assert_eq!(
+            commands.get_alert_factor(metric.id).await.unwrap(),
+            Some(DEFAULT_ALERT_FACTOR)
+        );
+
+        let date = |month, day| {
+            NaiveDate::from_ymd_opt(2023, month, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        // 1 per day
+        for (month, day, value) in [(9, 1, 100), (9, 11, 110), (10, 1, 130)] {
+            assert!(commands
+                .submit_readings(vec![(metric.id, value)], date(month, day))
+                .await
+                .unwrap()
+                .0
+                .is_empty());
+        }
+        // 2 per day is not more than twice the average
+        assert!(commands
+            .submit_readings(vec![(metric.id, 150)], date(10, 11))
+            .await
+            .unwrap()
+            .0
+            .is_empty());
+        // the average is 50 in 40 days, now 30 in 5 days
+        let (alerts, _) = commands
+            .submit_readings(vec![(metric.id, 180)], date(10, 16))
+            .await
+            .unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].metric.name, "cold");
+        assert_eq!(alerts[0].daily, 6.0);
+        assert_eq!(alerts[0].average, 1.25);
+
+        assert!(commands
+            .set_alert_factor(metric.id, Some(10.0))
+            .await
+            .is_ok());
+        assert_eq!(
+            commands.get_alert_factor(metric.id).await.unwrap(),
+            Some(10.0)
+        );
+        assert!(commands
+            .submit_readings(vec![(metric.id, 250)], date(10, 21))
+            .await
+            .unwrap()
+            .0
+            .is_empty());
+
+        assert!(commands.set_alert_factor(metric.id, None).await.is_ok());
+        assert_eq!(commands.get_alert_factor(metric.id).await.unwrap(), None);
+        assert!(commands
+            .submit_readings(vec![(metric.id, 1000)], date(10, 22))
+            .await
+            .unwrap()
+            .0
+            .is_empty());
+
+        assert_eq!(
+            commands.set_alert_factor(metric.id, Some(0.5)).await,
+            Err(CommandError::InvalidAlertFactor)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_counter_rollover() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("power").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert(Metric::new("day", meter.id, 30))
+            .await
+            .unwrap();
+        let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+
+        assert_eq!(consumption(9990, 20, Some(4)), 30);
+        assert_eq!(consumption(9990, 20, None), 0);
+        assert_eq!(consumption(20, 9990, Some(4)), 9970);
+        let readings = [9000, 9990, 20, 100].map(|value| Reading {
+            id: None,
+            added: NaiveDateTime::default(),
+            value,
+            initial: false,
+        });
+        assert_eq!(total_consumption(&readings, Some(4)), 1100);
+
+        assert_eq!(commands.get_digits(metric.id).await.unwrap(), None);
+        assert_eq!(
+            commands.set_digits(metric.id, Some(0)).await,
+            Err(CommandError::InvalidDigits)
+        );
+        assert_eq!(
+            commands.set_digits(metric.id, Some(MAX_DIGITS + 1)).await,
+            Err(CommandError::InvalidDigits)
+        );
+        assert!(commands.set_digits(metric.id, Some(4)).await.is_ok());
+        assert_eq!(commands.get_digits(metric.id).await.unwrap(), Some(4));
+
+        let date = |month, day| {
+            NaiveDate::from_ymd_opt(2023, month, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        assert_eq!(
+            commands.check_reading(metric.id, 9900).await.unwrap(),
+            ReadingCheck::Ok
+        );
+        commands
+            .submit_readings(vec![(metric.id, 9900)], date(9, 1))
+            .await
+            .unwrap();
+        assert_eq!(
+            commands.check_reading(metric.id, 10000).await.unwrap(),
+            ReadingCheck::TooLarge(4)
+        );
+        assert_eq!(
+            commands.check_reading(metric.id, 9950).await.unwrap(),
+            ReadingCheck::Ok
+        );
+        assert_eq!(
+            commands.check_reading(metric.id, 50).await.unwrap(),
+            ReadingCheck::Rollover(9900)
+        );
+        commands
+            .submit_readings(vec![(metric.id, 9990)], date(9, 15))
+            .await
+            .unwrap();
+        commands
+            .submit_readings(vec![(metric.id, 50)], date(9, 30))
+            .await
+            .unwrap();
+
+        let report = commands
+            .monthly_report(NaiveDate::from_ymd_opt(2023, 9, 1).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(report.lines.len(), 1);
+        assert_eq!(report.lines[0].consumption, 150);
+
+        // without digits the value is taken as is
+        assert!(commands.set_digits(metric.id, None).await.is_ok());
+        assert_eq!(
+            commands.check_reading(metric.id, 10).await.unwrap(),
+            ReadingCheck::Ok
+        );
+    }
+
+    #[tokio::test]
+    async fn test_submit_checked_reading() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("water").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert(Metric::new("cold", meter.id, 40))
+            .await
+            .unwrap();
+        let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+        assert!(commands.set_digits(metric.id, Some(3)).await.is_ok());
+
+        let added = NaiveDate::from_ymd_opt(2023, 9, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let submit = |metric_id, value, rollover| {
+            commands.submit_checked_reading(metric_id, value, rollover, added)
+        };
+        assert!(submit(metric.id, 990, false).await.is_ok());
+        assert_eq!(
+            submit(metric.id, 1000, false).await.err(),
+            Some(CommandError::ReadingTooLarge(999))
+        );
+        assert_eq!(
+            submit(metric.id, 10, false).await.err(),
+            Some(CommandError::ReadingTooSmall(990))
+        );
+        assert!(submit(metric.id, 10, true).await.is_ok());
+        assert_eq!(
+            submit(100, 10, false).await.err(),
+            Some(CommandError::MetricNotFound(100))
+        );
+
+        assert!(commands.archive_meter(meter.id).await.is_ok());
+        assert_eq!(
+            submit(metric.id, 20, false).await.err(),
+            Some(CommandError::MeterArchived(meter.id))
+        );
+        let values = commands.list_readings_page(metric.id, 0, 10).await.unwrap();
+        assert_eq!(values.total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_replace_meter() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("power").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        for name in ["day", "night"] {
+            commands
+                .ops
+                .insert(Metric::new(name, meter.id, 10))
+                .await
+                .unwrap();
+        }
+        let metrics = commands.list_metrics(meter.id).await.unwrap();
+        let (day, night) = (metrics[0].id, metrics[1].id);
+
+        let date = |month, day| {
+            NaiveDate::from_ymd_opt(2023, month, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        for (month, value) in [(8, 1000), (9, 1100)] {
+            commands
+                .submit_readings(vec![(day, value), (night, value / 2)], date(month, 25))
+                .await
+                .unwrap();
+        }
+        assert!(commands
+            .replace_meter(vec![(day, 1150, 0), (night, 575, 10)], date(10, 10))
+            .await
+            .is_ok());
+        commands
+            .submit_readings(vec![(day, 30), (night, 40)], date(10, 25))
+            .await
+            .unwrap();
+
+        // the values are compared with the new counter
+        assert!(commands.set_digits(day, Some(4)).await.is_ok());
+        assert_eq!(
+            commands.check_reading(day, 20).await.unwrap(),
+            ReadingCheck::Rollover(30)
+        );
+        assert!(commands.set_digits(day, None).await.is_ok());
+
+        let replacements = commands.list_replacements(day).await.unwrap();
+        assert_eq!(replacements.len(), 1);
+        assert_eq!(replacements[0].old_value, 1150);
+        assert_eq!(replacements[0].new_value, 0);
+
+        let report = commands
+            .monthly_report(NaiveDate::from_ymd_opt(2023, 10, 1).unwrap())
+            .await
+            .unwrap();
+        let consumption = report
+            .lines
+            .iter()
+            .map(|x| (x.metric.as_str(), x.start, x.end, x.consumption))
+            .collect::<Vec<(&str, u32, u32, u32)>>();
+        assert_eq!(
+            consumption,
+            vec![("day", 1100, 30, 80), ("night", 550, 40, 55)]
+        );
+
+        let consumption = commands.monthly_consumption(meter.id, None).await.unwrap();
+        assert_eq!(
+            consumption[0].1,
+            vec![
+                (NaiveDate::from_ymd_opt(2023, 8, 1).unwrap(), 0),
+                (NaiveDate::from_ymd_opt(2023, 9, 1).unwrap(), 100),
+                (NaiveDate::from_ymd_opt(2023, 10, 1).unwrap(), 80),
+            ]
+        );
+
+        // an abnormal consumption is found across the replacement too
+        let (alerts, _) = commands
+            .submit_readings(vec![(day, 1030), (night, 41)], date(10, 26))
+            .await
+            .unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].metric.id, day);
+
+        assert!(commands.delete_meter(meter.id).await.is_ok());
+        assert!(commands.list_replacements(day).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reading_photos() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("power").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        for name in ["day", "night"] {
+            commands
+                .ops
+                .insert(Metric::new(name, meter.id, 10))
+                .await
+                .unwrap();
+        }
+        let metrics = commands.list_metrics(meter.id).await.unwrap();
+        let (day, night) = (metrics[0].id, metrics[1].id);
+
+        let added = NaiveDate::from_ymd_opt(2023, 10, 25)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        assert!(commands
+            .submit_readings_with_photos(
+                vec![
+                    (
+                        day,
+                        100,
+                        Some(ReadingPhoto::new("file1", Some("photos/1.jpg".to_string())))
+                    ),
+                    (night, 50, None),
+                ],
+                added,
+            )
+            .await
+            .is_ok());
+
+        let values = commands.list_readings_page(day, 0, 10).await.unwrap().items;
+        let photo = commands
+            .get_reading_photo(values[0].id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(photo.metric_id, day);
+        assert_eq!(photo.file_id, "file1");
+        assert_eq!(photo.path.as_deref(), Some("photos/1.jpg"));
+        assert_eq!(commands.list_reading_photos(day).await.unwrap().len(), 1);
+
+        let values = commands
+            .list_readings_page(night, 0, 10)
+            .await
+            .unwrap()
+            .items;
+        assert_eq!(values.len(), 1);
+        assert!(commands
+            .get_reading_photo(values[0].id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(commands
+            .list_reading_photos(night)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(commands.delete_meter(meter.id).await.is_ok());
+        assert!(commands.list_reading_photos(day).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_edit_readings() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.add_meter("water").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert(Metric::new("cold", meter.id, 30))
+            .await
+            .unwrap();
+        let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+
+        let date = |month, day| {
+            NaiveDate::from_ymd_opt(2023, month, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        for (month, value) in [(8, 100), (9, 1500), (10, 200)] {
+            commands
+                .submit_readings_with_photos(
+                    vec![(metric.id, value, Some(ReadingPhoto::new("file", None)))],
+                    date(month, 1),
+                )
+                .await
+                .unwrap();
+        }
+        let values = commands
+            .list_readings_page(metric.id, 0, 10)
+            .await
+            .unwrap()
+            .items;
+        let (first, typo, last) = (values[2].id, values[1].id, values[0].id);
+
+        assert_eq!(
+            commands.update_reading(typo, 50).await,
+            Err(CommandError::ReadingTooSmall(100))
+        );
+        assert_eq!(
+            commands.update_reading(typo, 250).await,
+            Err(CommandError::ReadingTooLarge(200))
+        );
+        assert!(commands.update_reading(typo, 150).await.is_ok());
+        assert_eq!(commands.get_reading(typo).await.unwrap().value, 150);
+        assert!(commands.update_reading(first, 0).await.is_ok());
+        assert!(commands.update_reading(last, 1000).await.is_ok());
+        assert_eq!(
+            commands.update_reading(100, 1).await,
+            Err(CommandError::ReadingNotFound(100))
+        );
+
+        // with known digits a smaller value may stay after a rollover
+        assert!(commands.set_digits(metric.id, Some(3)).await.is_ok());
+        assert!(commands.update_reading(last, 900).await.is_ok());
+        commands
+            .submit_readings(vec![(metric.id, 30)], date(11, 1))
+            .await
+            .unwrap();
+        let rollover = commands
+            .list_readings_page(metric.id, 0, 1)
+            .await
+            .unwrap()
+            .items[0]
+            .id;
+        assert!(commands.update_reading(rollover, 40).await.is_ok());
+        assert_eq!(
+            commands.update_reading(rollover, 1000).await,
+            Err(CommandError::ReadingTooLarge(999))
+        );
+        assert_eq!(
+            commands.update_reading(last, 10).await,
+            Err(CommandError::ReadingTooSmall(150))
+        );
+
+        assert!(commands.delete_reading(typo).await.is_ok());
+        assert_eq!(
+            commands.get_reading(typo).await.err(),
+            Some(CommandError::ReadingNotFound(typo))
+        );
+        assert!(commands.get_reading_photo(typo).await.unwrap().is_none());
+        assert_eq!(
+            commands.list_reading_photos(metric.id).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_audit() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        let user_commands = commands.for_user(42);
+        assert!(user_commands.add_meter("water").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        assert!(user_commands.archive_meter(meter.id).await.is_ok());
+        // bookkeeping isn't audited
+        assert!(user_commands.set_user_language(42, "en").await.is_ok());
+        assert!(user_commands.set_user_language(42, "uk").await.is_ok());
+        assert!(commands.set_reminder(1, 10, 20, 9, "en").await.is_ok());
+        let reminder = commands.get_reminder(1).await.unwrap().unwrap();
+        let date = NaiveDate::from_ymd_opt(2023, 12, 10).unwrap();
+        assert!(commands.mark_reminder_sent(reminder, date).await.is_ok());
+        assert!(commands.delete_meter(meter.id).await.is_ok());
+
+        let page = commands.list_audit_page(0, 10).await.unwrap();
+        assert_eq!(page.total, 4);
+        let records = page
+            .items
+            .iter()
+            .map(|x| (x.user_id, x.entity.as_str(), x.action.as_str()))
+            .collect::<Vec<(Option<i64>, &str, &str)>>();
+        assert_eq!(
+            records,
+            vec![
+                (None, "meter", "delete"),
+                (None, "reminder", "insert"),
+                (Some(42), "meter", "update"),
+                (Some(42), "meter", "insert"),
+            ]
+        );
+        assert_eq!(page.items[0].entity_id, meter.id);
+        assert!(page.items[0]
+            .before
+            .as_deref()
+            .unwrap()
+            .contains("\"name\":\"water\""));
+        assert!(page.items[0].after.is_none());
+        assert!(page.items[1].before.is_none());
+        assert!(page.items[1]
+            .after
+            .as_deref()
+            .unwrap()
+            .contains("\"last_sent\":null"));
+        assert!(page.items[2]
+            .before
+            .as_deref()
+            .unwrap()
+            .contains("\"archived\":0"));
+        assert!(page.items[2]
+            .after
+            .as_deref()
+            .unwrap()
+            .contains("\"archived\":1"));
+        assert!(page.items[3].before.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_undo() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+        let user_commands = commands.for_user(7);
+        let now = || Local::now().naive_local();
+
+        let action = user_commands.add_meter("water").await.unwrap().unwrap();
+        assert_eq!(
+            commands.for_user(8).undo(action, now()).await,
+            Err(CommandError::CannotUndo(action))
+        );
+        assert!(user_commands.undo(action, now()).await.is_ok());
+        assert!(commands.list_meters().await.unwrap().is_empty());
+        assert_eq!(
+            user_commands.undo(action, now()).await,
+            Err(CommandError::CannotUndo(action))
+        );
+
+        assert!(commands.add_meter("water").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert(Metric::new("cold", meter.id, 30))
+            .await
+            .unwrap();
+        let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+        let date = NaiveDate::from_ymd_opt(2023, 9, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let (_, submitted) = user_commands
+            .submit_readings_with_photos(
+                vec![(metric.id, 100, Some(ReadingPhoto::new("file", None)))],
+                date,
+            )
+            .await
+            .unwrap();
+        let submitted = submitted.unwrap();
+        let reading = commands
+            .list_readings_page(metric.id, 0, 10)
+            .await
+            .unwrap()
+            .items
+            .remove(0);
+
+        let updated = user_commands
+            .update_reading(reading.id, 150)
+            .await
+            .unwrap()
+            .unwrap();
+        // a button of an earlier change reverts only that change
+        assert_eq!(
+            user_commands.undo(submitted, now()).await,
+            Err(CommandError::UndoConflict(submitted))
+        );
+        assert_eq!(
+            user_commands
+                .undo(updated, now() + Duration::minutes(UNDO_MINUTES + 1))
+                .await,
+            Err(CommandError::UndoExpired(updated))
+        );
+        assert!(user_commands.undo(updated, now()).await.is_ok());
+        assert_eq!(commands.get_reading(reading.id).await.unwrap().value, 100);
+
+        let deleted = user_commands
+            .delete_reading(reading.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(commands.get_reading(reading.id).await.is_err());
+        assert!(user_commands.undo(deleted, now()).await.is_ok());
+        let restored = commands.get_reading(reading.id).await.unwrap();
+        assert_eq!(restored.value, 100);
+        assert_eq!(restored.added, date);
+        assert_eq!(
+            commands.list_reading_photos(metric.id).await.unwrap().len(),
+            1
+        );
+
+        let records = commands.list_audit_page(0, 10).await.unwrap().items;
+        assert_eq!(records[0].action, "undo");
+        assert_eq!(records[0].user_id, Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_reminders() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert_eq!(
+            commands.set_reminder(1, 0, 25, 10, "en").await,
+            Err(CommandError::InvalidReminder)
+        );
+        assert!(commands.set_reminder(1, 26, 25, 10, "en").await.is_err());
+        assert!(commands.set_reminder(1, 15, 32, 10, "en").await.is_err());
+        assert!(commands.set_reminder(1, 15, 25, 24, "en").await.is_err());
+
+        assert!(commands.set_reminder(1, 10, 20, 9, "en").await.is_ok());
+        assert!(commands.set_reminder(1, 15, 25, 10, "uk").await.is_ok());
+        assert!(commands.set_reminder(2, 1, 5, 10, "en").await.is_ok());
+        let reminder = commands.get_reminder(1).await.unwrap().unwrap();
+        assert_eq!(
+            (reminder.first_day, reminder.last_day, reminder.hour),
+            (15, 25, 10)
+        );
+        assert_eq!(reminder.language.as_deref(), Some("uk"));
+
+        assert!(commands.add_meter("meter1").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert(Metric::new("day", meter.id, 100))
+            .await
+            .unwrap();
+        let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+
+        let time = |day, hour| {
+            NaiveDate::from_ymd_opt(2023, 12, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        // outside of the period or too early
+        assert!(commands
+            .due_reminders(time(14, 12))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(commands
+            .due_reminders(time(26, 12))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(commands
+            .due_reminders(time(15, 9))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let due = commands.due_reminders(time(15, 10)).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0.chat_id, 1);
+        assert_eq!(due[0].1[0].name, "day");
+
+        // sent today already, due again tomorrow
+        assert!(commands
+            .mark_reminder_sent(due[0].0.clone(), time(15, 10).date())
+            .await
+            .is_ok());
+        assert!(commands
+            .due_reminders(time(15, 11))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(commands.due_reminders(time(16, 10)).await.unwrap().len(), 1);
+
+        // readings of the previous period don't count
+        assert!(commands
+            .submit_readings(vec![(metric.id, 10)], time(14, 10))
+            .await
+            .is_ok());
+        assert_eq!(commands.due_reminders(time(16, 10)).await.unwrap().len(), 1);
+        assert!(commands
+            .submit_readings(vec![(metric.id, 20)], time(16, 9))
+            .await
+            .is_ok());
+        assert!(commands
+            .due_reminders(time(16, 10))
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(commands.delete_reminder(1).await.is_ok());
+        assert!(commands.get_reminder(1).await.unwrap().is_none());
+        assert!(commands.get_reminder(2).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_reminders_in_short_months() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+        assert!(commands.add_meter("meter1").await.is_ok());
+        let meter = commands.list_meters().await.unwrap().remove(0);
+        commands
+            .ops
+            .insert(Metric::new("day", meter.id, 100))
+            .await
+            .unwrap();
+        let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+
+        let time = |month, day| {
+            NaiveDate::from_ymd_opt(2023, month, day)
+                .unwrap()
+                .and_hms_opt(10, 0, 0)
+                .unwrap()
+        };
+        // the days past the end of February mean its last day
+        assert!(commands.set_reminder(1, 30, 31, 9, "en").await.is_ok());
+        assert!(commands
+            .due_reminders(time(2, 27))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(commands.due_reminders(time(2, 28)).await.unwrap().len(), 1);
+        assert!(commands
+            .due_reminders(time(3, 28))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(commands.due_reminders(time(3, 30)).await.unwrap().len(), 1);
+        assert!(commands
+            .due_reminders(time(4, 29))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(commands.due_reminders(time(4, 30)).await.unwrap().len(), 1);
+
+        // and the period starts no later than the last day
+        assert!(commands.set_reminder(1, 29, 31, 9, "en").await.is_ok());
+        assert!(commands
+            .submit_readings(vec![(metric.id, 10)], time(2, 27))
+            .await
+            .is_ok());
+        assert_eq!(commands.due_reminders(time(2, 28)).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_user_language() {
+        let commands = Commands::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(commands.get_connection()).await;
+
+        assert!(commands.get_user_language(1).await.unwrap().is_none());
+
+        assert!(commands.set_user_language(1, "ru").await.is_ok());
+        assert!(commands.set_user_language(2, "en").await.is_ok());
+        assert_eq!(commands.get_user_language(1).await.unwrap().unwrap(), "ru");
+
+        assert!(commands.set_user_language(1, "uk").await.is_ok());
+        assert_eq!(commands.get_user_language(1).await.unwrap().unwrap(), "uk");
+        assert_eq!(commands.get_user_language(2).await.unwrap().unwrap(), "en");
+    }
 }
@@ -1,7 +1,14 @@
-use super::structs::{Meter, Metric, MetricValue, TableName};
+use super::structs::{
+    AuditRecord, Meter, Metric, MetricAlert, MetricCapacity, MetricReplacement, MetricValue,
+    ReadingPhoto, Reminder, TableName, UndoAction, UndoStep, UserSettings,
+};
 use tokio_rusqlite::Connection;
 
-pub trait CreateTable {
+/// Version of the tables, stored as the `user_version` of the database so that
+/// backups of other versions aren't restored.
+pub const SCHEMA_VERSION: u32 = 9;
+
+pub(crate) trait CreateTable {
     async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize>;
 }
 
@@ -13,7 +20,8 @@ impl CreateTable for Meter {
                     std::format!(
                         "CREATE TABLE {} (
                                 id INTEGER PRIMARY KEY,
-                                name TEXT NOT NULL
+                                name TEXT NOT NULL,
+                                archived INTEGER NOT NULL DEFAULT 0
                         )",
                         Meter::TABLE_NAME
                     )
@@ -71,6 +79,217 @@ impl CreateTable for MetricValue {
     }
 }
 
+impl CreateTable for Reminder {
+    async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize> {
+        connection
+            .call(|connection| {
+                Ok(connection.execute(
+                    std::format!(
+                        "CREATE TABLE {} (
+                            id INTEGER PRIMARY KEY,
+                            chat_id INTEGER NOT NULL UNIQUE,
+                            first_day INTEGER,
+                            last_day INTEGER,
+                            hour INTEGER,
+                            last_sent STRING,
+                            language TEXT
+                        )",
+                        Reminder::TABLE_NAME
+                    )
+                    .as_str(),
+                    (),
+                )?)
+            })
+            .await
+    }
+}
+
+impl CreateTable for UserSettings {
+    async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize> {
+        connection
+            .call(|connection| {
+                Ok(connection.execute(
+                    std::format!(
+                        "CREATE TABLE {} (
+                            id INTEGER PRIMARY KEY,
+                            user_id INTEGER NOT NULL UNIQUE,
+                            language TEXT NOT NULL
+                        )",
+                        UserSettings::TABLE_NAME
+                    )
+                    .as_str(),
+                    (),
+                )?)
+            })
+            .await
+    }
+}
+
+impl CreateTable for MetricAlert {
+    async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize> {
+        connection
+            .call(|connection| {
+                Ok(connection.execute(
+                    std::format!(
+                        "CREATE TABLE {} (
+                            id INTEGER PRIMARY KEY,
+                            metric_id INTEGER NOT NULL UNIQUE,
+                            factor REAL,
+                            FOREIGN KEY(metric_id) REFERENCES metric(id)
+                        )",
+                        MetricAlert::TABLE_NAME
+                    )
+                    .as_str(),
+                    (),
+                )?)
+            })
+            .await
+    }
+}
+
+impl CreateTable for MetricCapacity {
+    async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize> {
+        connection
+            .call(|connection| {
+                Ok(connection.execute(
+                    std::format!(
+                        "CREATE TABLE {} (
+                            id INTEGER PRIMARY KEY,
+                            metric_id INTEGER NOT NULL UNIQUE,
+                            digits INTEGER NOT NULL,
+                            FOREIGN KEY(metric_id) REFERENCES metric(id)
+                        )",
+                        MetricCapacity::TABLE_NAME
+                    )
+                    .as_str(),
+                    (),
+                )?)
+            })
+            .await
+    }
+}
+
+impl CreateTable for MetricReplacement {
+    async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize> {
+        connection
+            .call(|connection| {
+                Ok(connection.execute(
+                    std::format!(
+                        "CREATE TABLE {} (
+                            id INTEGER PRIMARY KEY,
+                            metric_id INTEGER NOT NULL,
+                            old_value INTEGER NOT NULL,
+                            new_value INTEGER NOT NULL,
+                            replaced STRING NOT NULL,
+                            FOREIGN KEY(metric_id) REFERENCES metric(id)
+                        )",
+                        MetricReplacement::TABLE_NAME
+                    )
+                    .as_str(),
+                    (),
+                )?)
+            })
+            .await
+    }
+}
+
+impl CreateTable for ReadingPhoto {
+    async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize> {
+        connection
+            .call(|connection| {
+                Ok(connection.execute(
+                    std::format!(
+                        "CREATE TABLE {} (
+                            id INTEGER PRIMARY KEY,
+                            metric_value_id INTEGER NOT NULL UNIQUE,
+                            metric_id INTEGER NOT NULL,
+                            file_id TEXT NOT NULL,
+                            path TEXT,
+                            FOREIGN KEY(metric_value_id) REFERENCES metric_value(id),
+                            FOREIGN KEY(metric_id) REFERENCES metric(id)
+                        )",
+                        ReadingPhoto::TABLE_NAME
+                    )
+                    .as_str(),
+                    (),
+                )?)
+            })
+            .await
+    }
+}
+
+impl CreateTable for AuditRecord {
+    async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize> {
+        connection
+            .call(|connection| {
+                Ok(connection.execute(
+                    std::format!(
+                        "CREATE TABLE {} (
+                            id INTEGER PRIMARY KEY,
+                            user_id INTEGER,
+                            changed STRING NOT NULL,
+                            entity TEXT NOT NULL,
+                            entity_id INTEGER NOT NULL,
+                            action TEXT NOT NULL,
+                            before TEXT,
+                            after TEXT
+                        )",
+                        AuditRecord::TABLE_NAME
+                    )
+                    .as_str(),
+                    (),
+                )?)
+            })
+            .await
+    }
+}
+
+impl CreateTable for UndoAction {
+    async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize> {
+        connection
+            .call(|connection| {
+                Ok(connection.execute(
+                    std::format!(
+                        "CREATE TABLE {} (
+                            id INTEGER PRIMARY KEY,
+                            user_id INTEGER,
+                            created STRING NOT NULL,
+                            undone INTEGER NOT NULL
+                        )",
+                        UndoAction::TABLE_NAME
+                    )
+                    .as_str(),
+                    (),
+                )?)
+            })
+            .await
+    }
+}
+
+impl CreateTable for UndoStep {
+    async fn create_table(connection: &Connection) -> tokio_rusqlite::Result<usize> {
+        connection
+            .call(|connection| {
+                Ok(connection.execute(
+                    std::format!(
+                        "CREATE TABLE {} (
+                            id INTEGER PRIMARY KEY,
+                            action_id INTEGER NOT NULL,
+                            entity TEXT NOT NULL,
+                            entity_id INTEGER NOT NULL,
+                            row TEXT,
+                            FOREIGN KEY(action_id) REFERENCES undo_action(id)
+                        )",
+                        UndoStep::TABLE_NAME
+                    )
+                    .as_str(),
+                    (),
+                )?)
+            })
+            .await
+    }
+}
+
 pub async fn table_exists<T: TableName>(connection: &Connection) -> bool {
     let check_result = connection
         .call(|connection| {
@@ -91,7 +310,42 @@ pub async fn table_exists<T: TableName>(connection: &Connection) -> bool {
     check_result.unwrap_or(false)
 }
 
-pub async fn create_table_if_does_not_exist<T>(connection: &Connection)
+async fn column_exists<T: TableName>(connection: &Connection, column: &str) -> bool {
+    let column = column.to_string();
+    connection
+        .call(move |connection| {
+            let mut statement =
+                connection.prepare(&std::format!("PRAGMA table_info({})", T::TABLE_NAME))?;
+            let columns = statement
+                .query_map([], |row| row.get::<usize, String>(1))?
+                .collect::<Result<Vec<String>, _>>()?;
+            Ok(columns.contains(&column))
+        })
+        .await
+        .unwrap_or(false)
+}
+
+/// Adds a column to a table created before the column was introduced.
+async fn add_column_if_does_not_exist<T: TableName>(
+    connection: &Connection,
+    column: &str,
+    definition: &str,
+) {
+    if !column_exists::<T>(connection, column).await {
+        let query = std::format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            T::TABLE_NAME,
+            column,
+            definition
+        );
+        connection
+            .call(move |connection| Ok(connection.execute(&query, ())?))
+            .await
+            .unwrap();
+    }
+}
+
+pub(crate) async fn create_table_if_does_not_exist<T>(connection: &Connection)
 where
     T: TableName,
     T: CreateTable,
@@ -103,15 +357,37 @@ where
 
 pub async fn create_tables_if_do_not_exist(connection: &Connection) {
     create_table_if_does_not_exist::<Meter>(connection).await;
+    add_column_if_does_not_exist::<Meter>(connection, "archived", "INTEGER NOT NULL DEFAULT 0")
+        .await;
     create_table_if_does_not_exist::<Metric>(connection).await;
     create_table_if_does_not_exist::<MetricValue>(connection).await;
+    create_table_if_does_not_exist::<Reminder>(connection).await;
+    add_column_if_does_not_exist::<Reminder>(connection, "language", "TEXT").await;
+    create_table_if_does_not_exist::<UserSettings>(connection).await;
+    create_table_if_does_not_exist::<MetricAlert>(connection).await;
+    create_table_if_does_not_exist::<MetricCapacity>(connection).await;
+    create_table_if_does_not_exist::<MetricReplacement>(connection).await;
+    create_table_if_does_not_exist::<ReadingPhoto>(connection).await;
+    create_table_if_does_not_exist::<AuditRecord>(connection).await;
+    create_table_if_does_not_exist::<UndoAction>(connection).await;
+    create_table_if_does_not_exist::<UndoStep>(connection).await;
+    connection
+        .call(|connection| Ok(connection.pragma_update(None, "user_version", SCHEMA_VERSION)?))
+        .await
+        .unwrap();
 }
 
 #[cfg(test)]
 mod test {
-    use super::{create_table_if_does_not_exist, create_tables_if_do_not_exist, table_exists};
+    use super::{
+        column_exists, create_table_if_does_not_exist, create_tables_if_do_not_exist, table_exists,
+        SCHEMA_VERSION,
+    };
     use crate::database::create::CreateTable;
-    use crate::database::structs::{Meter, Metric, MetricValue};
+    use crate::database::structs::{
+        AuditRecord, Meter, Metric, MetricAlert, MetricCapacity, MetricReplacement, MetricValue,
+        ReadingPhoto, Reminder, UndoAction, UndoStep, UserSettings,
+    };
     use tokio_rusqlite::Connection;
 
     #[tokio::test]
@@ -130,6 +406,33 @@ mod test {
 
         MetricValue::create_table(&connection).await.unwrap();
         assert!(table_exists::<MetricValue>(&connection).await);
+
+        Reminder::create_table(&connection).await.unwrap();
+        assert!(table_exists::<Reminder>(&connection).await);
+
+        UserSettings::create_table(&connection).await.unwrap();
+        assert!(table_exists::<UserSettings>(&connection).await);
+
+        MetricAlert::create_table(&connection).await.unwrap();
+        assert!(table_exists::<MetricAlert>(&connection).await);
+
+        MetricCapacity::create_table(&connection).await.unwrap();
+        assert!(table_exists::<MetricCapacity>(&connection).await);
+
+        MetricReplacement::create_table(&connection).await.unwrap();
+        assert!(table_exists::<MetricReplacement>(&connection).await);
+
+        ReadingPhoto::create_table(&connection).await.unwrap();
+        assert!(table_exists::<ReadingPhoto>(&connection).await);
+
+        AuditRecord::create_table(&connection).await.unwrap();
+        assert!(table_exists::<AuditRecord>(&connection).await);
+
+        UndoAction::create_table(&connection).await.unwrap();
+        assert!(table_exists::<UndoAction>(&connection).await);
+
+        UndoStep::create_table(&connection).await.unwrap();
+        assert!(table_exists::<UndoStep>(&connection).await);
     }
 
     #[tokio::test]
@@ -155,5 +458,92 @@ mod test {
         assert!(table_exists::<Meter>(&connection).await);
         assert!(table_exists::<Metric>(&connection).await);
         assert!(table_exists::<MetricValue>(&connection).await);
+        assert!(table_exists::<Reminder>(&connection).await);
+        assert!(table_exists::<UserSettings>(&connection).await);
+        assert!(table_exists::<MetricAlert>(&connection).await);
+        assert!(table_exists::<MetricCapacity>(&connection).await);
+        assert!(table_exists::<MetricReplacement>(&connection).await);
+        assert!(table_exists::<ReadingPhoto>(&connection).await);
+        assert!(table_exists::<AuditRecord>(&connection).await);
+        assert!(table_exists::<UndoAction>(&connection).await);
+        assert!(table_exists::<UndoStep>(&connection).await);
+
+        let version = connection
+            .call(|connection| {
+                Ok(connection.query_row("PRAGMA user_version", (), |row| row.get::<_, u32>(0))?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_add_archived_column() {
+        let connection = Connection::open_in_memory().await.unwrap();
+        connection
+            .call(|connection| {
+                Ok(connection.execute_batch(
+                    "CREATE TABLE meter (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+                    INSERT INTO meter (name) VALUES ('water');",
+                )?)
+            })
+            .await
+            .unwrap();
+        assert!(!column_exists::<Meter>(&connection, "archived").await);
+
+        create_tables_if_do_not_exist(&connection).await;
+        assert!(column_exists::<Meter>(&connection, "archived").await);
+        let archived = connection
+            .call(|connection| {
+                Ok(
+                    connection.query_row("SELECT archived FROM meter", (), |row| {
+                        row.get::<usize, bool>(0)
+                    })?,
+                )
+            })
+            .await
+            .unwrap();
+        assert!(!archived);
+
+        // nothing changes the second time
+        create_tables_if_do_not_exist(&connection).await;
+        assert!(column_exists::<Meter>(&connection, "archived").await);
+    }
+
+    #[tokio::test]
+    async fn test_add_reminder_language_column() {
+        let connection = Connection::open_in_memory().await.unwrap();
+        connection
+            .call(|connection| {
+                Ok(connection.execute_batch(
+                    "CREATE TABLE reminder (
+                        id INTEGER PRIMARY KEY,
+                        chat_id INTEGER NOT NULL UNIQUE,
+                        first_day INTEGER,
+                        last_day INTEGER,
+                        hour INTEGER,
+                        last_sent STRING
+                    );
+                    INSERT INTO reminder (chat_id, first_day, last_day, hour)
+                        VALUES (-100, 15, 25, 10);",
+                )?)
+            })
+            .await
+            .unwrap();
+        assert!(!column_exists::<Reminder>(&connection, "language").await);
+
+        create_tables_if_do_not_exist(&connection).await;
+        assert!(column_exists::<Reminder>(&connection, "language").await);
+        let language = connection
+            .call(|connection| {
+                Ok(
+                    connection.query_row("SELECT language FROM reminder", (), |row| {
+                        row.get::<usize, Option<String>>(0)
+                    })?,
+                )
+            })
+            .await
+            .unwrap();
+        assert_eq!(language, None);
     }
 }
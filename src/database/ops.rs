@@ -1,62 +1,548 @@
-use super::query::{Order, Query, WhereExprOperator};
-use super::structs::{FieldNames, FromRow, InsertValues, TableName};
+use super::create::SCHEMA_VERSION;
+use super::query::{Order, Query, Where, WhereExprOperator};
+use super::structs::{
+    AuditRecord, FieldNames, FromRow, InsertValues, Metric, MetricValue, TableName, UndoAction,
+    UndoStep, UpdateValues,
+};
+use chrono::{Duration, Local, NaiveDateTime};
+use rusqlite::types::Value;
+use rusqlite::{DatabaseName, OpenFlags};
+use std::cell::Cell;
+use std::collections::HashSet;
 use std::marker::{Send, Sync};
+use std::path::PathBuf;
 use tokio_rusqlite::{Connection, Error};
 
 pub type DatabaseResult<T> = Result<T, Error>;
 pub type DatabaseResultNoValue = DatabaseResult<()>;
+/// Id of the [`UndoAction`] reverting a change, if the change recorded one.
+pub type UndoActionId = Option<u32>;
 
+/// How long after a change it may be undone. Older [`UndoAction`] are deleted.
+pub const UNDO_MINUTES: i64 = 10;
+
+/// Access to the database, where every change is recorded as an
+/// [`AuditRecord`] of `user_id`, or of the bot itself if there is no user.
 pub struct Operations {
     connection: Connection,
+    user_id: Option<i64>,
+}
+
+/// Handle passed to the closure of [`Operations::transaction`]. Everything done
+/// through it is committed together or rolled back if the closure fails, and
+/// may be reverted later as a single [`UndoAction`].
+pub struct Transaction<'a> {
+    transaction: rusqlite::Transaction<'a>,
+    user_id: Option<i64>,
+    /// Whether the changes are audited and may be undone.
+    tracked: bool,
+    undo_action_id: Cell<Option<u32>>,
+}
+
+/// Whether the database is intact and has the tables of [`SCHEMA_VERSION`].
+fn is_valid_backup(connection: &rusqlite::Connection) -> bool {
+    let integrity =
+        connection.query_row("PRAGMA integrity_check", (), |row| row.get::<_, String>(0));
+    let version = connection.query_row("PRAGMA user_version", (), |row| row.get::<_, u32>(0));
+    matches!((integrity, version), (Ok(integrity), Ok(version)) if integrity == "ok" && version == SCHEMA_VERSION)
+}
+
+fn select<T: FromRow>(connection: &rusqlite::Connection, query: &str) -> DatabaseResult<Vec<T>> {
+    let mut statement = connection.prepare(query)?;
+    let result = statement
+        .query_map((), |row| Ok(T::from_row(row)))?
+        .collect::<Result<Vec<T>, _>>()?;
+    Ok(result)
+}
+
+/// Values of all columns of a row as stored, by column name.
+type RawRow = Vec<(String, Value)>;
+
+/// Encodes a row as a JSON object of its columns, the format of the states
+/// kept in [`AuditRecord`] and [`UndoStep`].
+fn encode_row(row: RawRow) -> String {
+    serde_json::Value::Object(
+        row.into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::Null => serde_json::Value::Null,
+                    Value::Integer(x) => x.into(),
+                    Value::Real(x) => x.into(),
+                    Value::Text(x) => x.into(),
+                    Value::Blob(x) => x.into(),
+                };
+                (name, value)
+            })
+            .collect(),
+    )
+    .to_string()
+}
+
+fn decode_row(row: &str) -> Option<RawRow> {
+    match serde_json::from_str::<serde_json::Value>(row).ok()? {
+        serde_json::Value::Object(values) => values
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    serde_json::Value::Null => Some(Value::Null),
+                    serde_json::Value::Number(x) => match x.as_i64() {
+                        Some(x) => Some(Value::Integer(x)),
+                        None => x.as_f64().map(Value::Real),
+                    },
+                    serde_json::Value::String(x) => Some(Value::Text(x)),
+                    serde_json::Value::Array(x) => x
+                        .into_iter()
+                        .map(|x| x.as_u64().and_then(|x| u8::try_from(x).ok()))
+                        .collect::<Option<Vec<u8>>>()
+                        .map(Value::Blob),
+                    serde_json::Value::Bool(_) | serde_json::Value::Object(_) => None,
+                };
+                value.map(|value| (name, value))
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+/// Change of the row `id`, with its states encoded by [`encode_row`].
+struct Change {
+    id: u32,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl Change {
+    fn new(id: u32, before: Option<RawRow>, after: Option<RawRow>) -> Self {
+        Change {
+            id,
+            before: before.map(encode_row),
+            after: after.map(encode_row),
+        }
+    }
+}
+
+impl Transaction<'_> {
+    /// Records the changes of the rows of the table, all with one statement.
+    fn audit(&self, entity: &str, action: &str, changes: &[Change]) -> DatabaseResultNoValue {
+        let changed = Local::now().naive_local();
+        let mut statement = self
+            .transaction
+            .prepare(&Query::insert::<AuditRecord>().to_string())?;
+        for change in changes {
+            let record = AuditRecord::new(
+                self.user_id,
+                &changed,
+                entity,
+                change.id,
+                action,
+                change.before.clone(),
+                change.after.clone(),
+            );
+            statement.execute(record.get_insert_values())?;
+        }
+        Ok(())
+    }
+
+    /// Records how to revert the changes of the rows of the table: by putting
+    /// the states before back, or by deleting the rows that didn't exist.
+    fn record_undo(&self, entity: &str, changes: &[Change]) -> DatabaseResultNoValue {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let action_id = match self.undo_action_id.get() {
+            Some(action_id) => action_id,
+            None => {
+                let now = Local::now().naive_local();
+                self.delete_expired_undo_actions(now - Duration::minutes(UNDO_MINUTES))?;
+                let action = UndoAction::new(self.user_id, &now);
+                self.transaction.execute(
+                    &Query::insert::<UndoAction>().to_string(),
+                    action.get_insert_values(),
+                )?;
+                let action_id = self.transaction.last_insert_rowid() as u32;
+                self.undo_action_id.set(Some(action_id));
+                action_id
+            }
+        };
+        let mut statement = self
+            .transaction
+            .prepare(&Query::insert::<UndoStep>().to_string())?;
+        for change in changes {
+            let step = UndoStep::new(action_id, entity, change.id, change.before.clone());
+            statement.execute(step.get_insert_values())?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the actions created before `created` with their steps, since
+    /// they can't be undone anymore. The newest action is kept without steps,
+    /// so that its id isn't given to the next one.
+    fn delete_expired_undo_actions(&self, created: NaiveDateTime) -> DatabaseResultNoValue {
+        self.transaction.execute(
+            &std::format!(
+                "DELETE FROM {} WHERE action_id IN (SELECT id FROM {} WHERE created < ?1)",
+                UndoStep::TABLE_NAME,
+                UndoAction::TABLE_NAME
+            ),
+            (created,),
+        )?;
+        self.transaction.execute(
+            &std::format!(
+                "DELETE FROM {0} WHERE created < ?1 AND id < (SELECT MAX(id) FROM {0})",
+                UndoAction::TABLE_NAME
+            ),
+            (created,),
+        )?;
+        Ok(())
+    }
+
+    fn record<T: TableName>(&self, action: &str, changes: Vec<Change>) -> DatabaseResultNoValue {
+        if !self.tracked {
+            return Ok(());
+        }
+        self.audit(T::TABLE_NAME, action, &changes)?;
+        self.record_undo(T::TABLE_NAME, &changes)
+    }
+
+    /// Ids and values of all columns of the rows selected by the query.
+    fn raw_rows(&self, query: &str) -> DatabaseResult<Vec<(u32, RawRow)>> {
+        let mut statement = self.transaction.prepare(query)?;
+        let names = statement
+            .column_names()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        let mut rows = statement.query(())?;
+        let mut result = Vec::new();
+        while let Some(row) = rows.next()? {
+            let values = names
+                .iter()
+                .enumerate()
+                .map(|(index, name)| Ok((name.clone(), row.get::<usize, Value>(index)?)))
+                .collect::<rusqlite::Result<RawRow>>()?;
+            result.push((row.get::<&str, u32>("id")?, values));
+        }
+        Ok(result)
+    }
+
+    fn raw_row(&self, table: &str, id: u32) -> DatabaseResult<Option<RawRow>> {
+        Ok(self
+            .raw_rows(&std::format!("SELECT * FROM {} WHERE id = {}", table, id))?
+            .into_iter()
+            .next()
+            .map(|(_, row)| row))
+    }
+
+    /// The action reverting the changes made so far, if any were recorded.
+    pub fn undo_action_id(&self) -> UndoActionId {
+        self.undo_action_id.get()
+    }
+
+    /// Inserts the entry and returns the id assigned to it.
+    pub fn insert<T: TableName + FieldNames + InsertValues>(
+        &self,
+        entry: T,
+    ) -> DatabaseResult<u32> {
+        self.transaction
+            .execute(&Query::insert::<T>().to_string(), entry.get_insert_values())?;
+        let id = self.transaction.last_insert_rowid() as u32;
+        let after = self.raw_row(T::TABLE_NAME, id)?;
+        self.record::<T>(AuditRecord::INSERT, vec![Change::new(id, None, after)])?;
+        Ok(id)
+    }
+
+    /// Inserts the entries with one prepared statement and records them
+    /// together as a single change.
+    pub fn insert_many<T: TableName + FieldNames + InsertValues>(
+        &self,
+        entries: Vec<T>,
+    ) -> DatabaseResultNoValue {
+        let mut statement = self
+            .transaction
+            .prepare(&Query::insert::<T>().to_string())?;
+        let mut ids = HashSet::new();
+        for entry in entries {
+            ids.insert(statement.insert(entry.get_insert_values())? as u32);
+        }
+        let (first, last) = match (ids.iter().min(), ids.iter().max()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Ok(()),
+        };
+        let changes = self
+            .raw_rows(
+                &Query::select::<T>()
+                    .filter(
+                        Where::new("id", WhereExprOperator::GreaterOrEqual, first).and(
+                            "id",
+                            WhereExprOperator::LessOrEqual,
+                            last,
+                        ),
+                    )
+                    .to_string(),
+            )?
+            .into_iter()
+            .filter(|(id, _)| ids.contains(id))
+            .map(|(id, row)| Change::new(id, None, Some(row)))
+            .collect();
+        self.record::<T>(AuditRecord::INSERT, changes)
+    }
+
+    pub fn update<T: TableName + FieldNames + UpdateValues>(
+        &self,
+        entry: T,
+    ) -> DatabaseResultNoValue {
+        let id = entry.get_id();
+        let before = self.raw_row(T::TABLE_NAME, id)?;
+        self.transaction
+            .execute(&Query::update::<T>().to_string(), entry.get_update_values())?;
+        if before.is_some() {
+            let after = self.raw_row(T::TABLE_NAME, id)?;
+            self.record::<T>(AuditRecord::UPDATE, vec![Change::new(id, before, after)])?;
+        }
+        Ok(())
+    }
+
+    pub fn get_all<T: TableName + FromRow>(&self) -> DatabaseResult<Vec<T>> {
+        select(&self.transaction, &Query::select::<T>().to_string())
+    }
+
+    pub fn find<T: TableName + FromRow>(&self, condition: Where) -> DatabaseResult<Vec<T>> {
+        select(
+            &self.transaction,
+            &Query::select::<T>().filter(condition).to_string(),
+        )
+    }
+
+    pub fn delete_by_id<T: TableName>(&self, id: u32) -> DatabaseResultNoValue {
+        self.delete_where::<T>(Where::new("id", WhereExprOperator::Equal, id))?;
+        Ok(())
+    }
+
+    /// Returns the number of deleted rows.
+    pub fn delete_where<T: TableName>(&self, condition: Where) -> DatabaseResult<usize> {
+        let rows = self.raw_rows(&Query::select::<T>().filter(condition.clone()).to_string())?;
+        let count = self
+            .transaction
+            .execute(&Query::delete::<T>().filter(condition).to_string(), ())?;
+        let changes = rows
+            .into_iter()
+            .map(|(id, row)| Change::new(id, Some(row), None))
+            .collect();
+        self.record::<T>(AuditRecord::DELETE, changes)?;
+        Ok(count)
+    }
 }
 
 impl Operations {
     pub async fn in_memory() -> DatabaseResult<Self> {
         match Connection::open_in_memory().await {
-            Ok(connection) => Ok(Operations { connection }),
+            Ok(connection) => Ok(Operations {
+                connection,
+                user_id: None,
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub async fn open(path: PathBuf) -> DatabaseResult<Self> {
+        match Connection::open(path).await {
+            Ok(connection) => Ok(Operations {
+                connection,
+                user_id: None,
+            }),
             Err(error) => Err(error),
         }
     }
 
+    /// The same database, where the changes are recorded as made by the user.
+    pub fn for_user(&self, user_id: i64) -> Self {
+        Operations {
+            connection: self.connection.clone(),
+            user_id: Some(user_id),
+        }
+    }
+
+    pub fn user_id(&self) -> Option<i64> {
+        self.user_id
+    }
+
     pub fn get_connection(&self) -> &Connection {
         &self.connection
     }
 
-    pub async fn insert<
-        T: Send + TableName + FieldNames + InsertValues + Clone + Send + Sync + 'static,
-    >(
-        &self,
-        entry: T,
-    ) -> DatabaseResultNoValue {
-        match self
-            .connection
+    /// Writes a consistent copy of the whole database to a new file at `path`.
+    pub async fn backup(&self, path: PathBuf) -> DatabaseResultNoValue {
+        self.connection
+            .call(move |connection| Ok(connection.backup(DatabaseName::Main, path, None)?))
+            .await
+    }
+
+    /// Replaces the whole database with the one in the file at `path`. Returns
+    /// false and leaves the database as is if the file isn't a valid backup.
+    pub async fn restore(&self, path: PathBuf) -> DatabaseResult<bool> {
+        self.connection
             .call(move |connection| {
-                Ok(connection
-                    .execute(&Query::insert::<T>().to_string(), entry.get_insert_values())?)
+                let source =
+                    rusqlite::Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+                if !is_valid_backup(&source) {
+                    return Ok(false);
+                }
+                drop(source);
+                connection.restore(
+                    DatabaseName::Main,
+                    path,
+                    None::<fn(rusqlite::backup::Progress)>,
+                )?;
+                Ok(true)
             })
             .await
-        {
-            Ok(_) => Ok(()),
-            Err(error) => Err(error),
-        }
     }
 
-    pub async fn delete_by_id<T: TableName>(&self, id: u32) -> DatabaseResultNoValue {
-        match self
-            .connection
+    pub async fn insert<T>(&self, entry: T) -> DatabaseResult<UndoActionId>
+    where
+        T: TableName + FieldNames + InsertValues + Send + Sync + 'static,
+    {
+        self.transaction(move |transaction| {
+            transaction.insert(entry)?;
+            Ok(transaction.undo_action_id())
+        })
+        .await
+    }
+
+    /// Overwrites all fields of the row with the id of `entry`.
+    pub async fn update<T>(&self, entry: T) -> DatabaseResult<UndoActionId>
+    where
+        T: TableName + FieldNames + UpdateValues + Send + Sync + 'static,
+    {
+        self.transaction(move |transaction| {
+            transaction.update(entry)?;
+            Ok(transaction.undo_action_id())
+        })
+        .await
+    }
+
+    /// Runs `function` inside a single SQLite transaction. The transaction is
+    /// committed if `function` succeeds and rolled back if it returns an error.
+    pub async fn transaction<F, R>(&self, function: F) -> DatabaseResult<R>
+    where
+        F: FnOnce(&Transaction) -> DatabaseResult<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.run_transaction(true, function).await
+    }
+
+    /// Like [`Operations::transaction`], but for the bot's own bookkeeping,
+    /// which is neither audited nor may be undone.
+    pub async fn bookkeeping<F, R>(&self, function: F) -> DatabaseResult<R>
+    where
+        F: FnOnce(&Transaction) -> DatabaseResult<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.run_transaction(false, function).await
+    }
+
+    async fn run_transaction<F, R>(&self, tracked: bool, function: F) -> DatabaseResult<R>
+    where
+        F: FnOnce(&Transaction) -> DatabaseResult<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let user_id = self.user_id;
+        self.connection
             .call(move |connection| {
-                Ok(connection.execute(
-                    &Query::delete::<T>()
-                        .where_("id", WhereExprOperator::Equal, id)
-                        .to_string(),
-                    (),
-                ))
+                let transaction = Transaction {
+                    transaction: connection.transaction()?,
+                    user_id,
+                    tracked,
+                    undo_action_id: Cell::new(None),
+                };
+                let result = function(&transaction)?;
+                transaction.transaction.commit()?;
+                Ok(result)
             })
             .await
-        {
-            Ok(_) => Ok(()),
-            Err(error) => Err(error),
-        }
+    }
+
+    pub async fn insert_many<T>(&self, entries: Vec<T>) -> DatabaseResult<UndoActionId>
+    where
+        T: TableName + FieldNames + InsertValues + Send + Sync + 'static,
+    {
+        self.transaction(move |transaction| {
+            transaction.insert_many(entries)?;
+            Ok(transaction.undo_action_id())
+        })
+        .await
+    }
+
+    pub async fn delete_by_id<T>(&self, id: u32) -> DatabaseResult<UndoActionId>
+    where
+        T: TableName + Send + Sync + 'static,
+    {
+        self.transaction(move |transaction| {
+            transaction.delete_by_id::<T>(id)?;
+            Ok(transaction.undo_action_id())
+        })
+        .await
+    }
+
+    /// Reverts all changes of the action, newest first, and marks it undone.
+    /// Reverting is audited but can't be reverted itself.
+    pub async fn undo(&self, action_id: u32) -> DatabaseResultNoValue {
+        self.transaction(move |transaction| {
+            let mut steps = transaction.find::<UndoStep>(Where::new(
+                "action_id",
+                WhereExprOperator::Equal,
+                action_id,
+            ))?;
+            steps.sort_by_key(|x| std::cmp::Reverse(x.id));
+            for step in steps {
+                let before = transaction.raw_row(&step.entity, step.entity_id)?;
+                match step.row.as_deref().map(decode_row) {
+                    Some(Some(row)) => {
+                        let names = row
+                            .iter()
+                            .map(|(name, _)| name.as_str())
+                            .collect::<Vec<&str>>()
+                            .join(", ");
+                        let placeholders = vec!["?"; row.len()].join(", ");
+                        transaction.transaction.execute(
+                            &std::format!(
+                                "INSERT OR REPLACE INTO {} ({}) VALUES ({})",
+                                step.entity,
+                                names,
+                                placeholders
+                            ),
+                            rusqlite::params_from_iter(row.into_iter().map(|(_, value)| value)),
+                        )?;
+                    }
+                    Some(None) => {
+                        return Err(Error::Other(
+                            std::format!("Wrong undo step {}", step.id).into(),
+                        ))
+                    }
+                    None => {
+                        transaction.transaction.execute(
+                            &std::format!("DELETE FROM {} WHERE id = ?1", step.entity),
+                            (step.entity_id,),
+                        )?;
+                    }
+                }
+                let after = transaction.raw_row(&step.entity, step.entity_id)?;
+                transaction.audit(
+                    &step.entity,
+                    AuditRecord::UNDO,
+                    &[Change::new(step.entity_id, before, after)],
+                )?;
+            }
+            transaction.transaction.execute(
+                &std::format!(
+                    "UPDATE {} SET undone = 1 WHERE id = ?1",
+                    UndoAction::TABLE_NAME
+                ),
+                (action_id,),
+            )?;
+            Ok(())
+        })
+        .await
     }
 
     pub async fn exists_by_name<T: TableName + FromRow>(
@@ -73,8 +559,7 @@ impl Operations {
                 )?;
                 let result = !statement
                     .query_map((), |row| Ok(T::from_row(row)))?
-                    .map(|x| x.unwrap())
-                    .collect::<Vec<T>>()
+                    .collect::<Result<Vec<T>, _>>()?
                     .is_empty();
                 Ok(result)
             })
@@ -85,60 +570,150 @@ impl Operations {
         }
     }
 
+    async fn select<T: FromRow + Send + Sync + 'static>(
+        &self,
+        query: String,
+    ) -> DatabaseResult<Vec<T>> {
+        self.connection
+            .call(move |connection| select(connection, &query))
+            .await
+    }
+
     pub async fn get_all<T: TableName + FromRow + Send + Sync + 'static>(
         &self,
     ) -> DatabaseResult<Vec<T>> {
+        self.select(Query::select::<T>().to_string()).await
+    }
+
+    pub async fn get_by_id<T: TableName + FromRow + Send + Sync + 'static>(
+        &self,
+        id: u32,
+    ) -> DatabaseResult<Option<T>> {
         match self
-            .connection
-            .call(|connection| {
-                let mut statement = connection.prepare(&Query::select::<T>().to_string())?;
-                let result = statement
-                    .query_map((), |row| Ok(T::from_row(row)))?
-                    .map(|x| x.unwrap())
-                    .collect();
-                Ok(result)
-            })
+            .select(
+                Query::select::<T>()
+                    .where_("id", WhereExprOperator::Equal, id)
+                    .to_string(),
+            )
             .await
         {
-            Ok(result) => Ok(result),
+            Ok(rows) => Ok(rows.into_iter().next()),
             Err(error) => Err(error),
         }
     }
 
+    pub async fn find<T: TableName + FromRow + Send + Sync + 'static>(
+        &self,
+        condition: Where,
+    ) -> DatabaseResult<Vec<T>> {
+        self.select(Query::select::<T>().filter(condition).to_string())
+            .await
+    }
+
+    /// Returns at most `limit` rows matching `condition`, skipping the first
+    /// `offset` of them in the given order. Rows equal in `order_by` are
+    /// ordered by id, so that pages neither repeat nor skip them.
+    pub async fn find_page<T: TableName + FromRow + Send + Sync + 'static>(
+        &self,
+        condition: Option<Where>,
+        order_by: &str,
+        order: Order,
+        offset: usize,
+        limit: usize,
+    ) -> DatabaseResult<Vec<T>> {
+        let query = Query::select::<T>().order_by(order_by, order.clone());
+        let query = match order_by {
+            "id" => query,
+            _ => query.order_by("id", order),
+        }
+        .limit(limit)
+        .offset(offset);
+        self.select(
+            match condition {
+                Some(condition) => query.filter(condition),
+                None => query,
+            }
+            .to_string(),
+        )
+        .await
+    }
+
     pub async fn get_last<T: TableName + FromRow + Send + Sync + 'static>(
         &self,
-    ) -> DatabaseResult<T> {
+    ) -> DatabaseResult<Option<T>> {
         match self
-            .connection
-            .call(|connection| {
-                let mut statement = connection.prepare(
-                    &Query::select::<T>()
-                        .order_by("id", Order::Descending)
-                        .limit(1)
-                        .to_string(),
-                )?;
-                let result = statement
-                    .query_map((), |row| Ok(T::from_row(row)))?
-                    .next()
-                    .unwrap()?;
-                Ok(result)
-            })
+            .select(
+                Query::select::<T>()
+                    .order_by("id", Order::Descending)
+                    .limit(1)
+                    .to_string(),
+            )
             .await
         {
-            Ok(result) => Ok(result),
+            Ok(rows) => Ok(rows.into_iter().next()),
             Err(error) => Err(error),
         }
     }
+
+    pub async fn count<T: TableName>(&self) -> DatabaseResult<usize> {
+        self.count_query(Query::count::<T>().to_string()).await
+    }
+
+    pub async fn count_where<T: TableName>(&self, condition: Where) -> DatabaseResult<usize> {
+        self.count_query(Query::count::<T>().filter(condition).to_string())
+            .await
+    }
+
+    /// Returns every metric of the meter together with its newest value, if any.
+    pub async fn get_latest_values(
+        &self,
+        meter_id: u32,
+    ) -> DatabaseResult<Vec<(Metric, Option<MetricValue>)>> {
+        self.connection
+            .call(move |connection| {
+                let mut statement = connection.prepare(&std::format!(
+                    "SELECT {metric}.*, {value}.* FROM {metric}
+                    LEFT JOIN {value} ON {value}.id = (
+                        SELECT id FROM {value} WHERE metric_id = {metric}.id
+                        ORDER BY added DESC, id DESC LIMIT 1
+                    )
+                    WHERE {metric}.meter_id = ?1
+                    ORDER BY {metric}.id",
+                    metric = Metric::TABLE_NAME,
+                    value = MetricValue::TABLE_NAME,
+                ))?;
+                let offset = Metric::get_field_names().len();
+                let result = statement
+                    .query_map((meter_id,), |row| {
+                        Ok((
+                            Metric::from_row(row),
+                            row.get::<usize, Option<u32>>(offset)?
+                                .map(|_| MetricValue::from_row_at(row, offset)),
+                        ))
+                    })?
+                    .collect::<Result<Vec<(Metric, Option<MetricValue>)>, _>>()?;
+                Ok(result)
+            })
+            .await
+    }
+
+    async fn count_query(&self, query: String) -> DatabaseResult<usize> {
+        self.connection
+            .call(move |connection| Ok(connection.query_row(&query, (), |row| row.get(0))?))
+            .await
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Operations;
+    use super::{Operations, UNDO_MINUTES};
     use crate::database::{
         create::create_tables_if_do_not_exist,
-        structs::{Meter, Metric, MetricValue},
+        query::{Order, Where, WhereExprOperator},
+        structs::{AuditRecord, Meter, Metric, MetricValue, UndoAction, UndoStep},
     };
-    use chrono::Local;
+    use chrono::{Duration, Local, NaiveDate};
+    use tokio_rusqlite::Error;
 
     #[tokio::test]
     async fn test_insert_and_get_all_records() {
@@ -192,10 +767,12 @@ mod test {
         let ops = Operations::in_memory().await.unwrap();
         create_tables_if_do_not_exist(ops.get_connection()).await;
 
+        assert!(ops.get_last::<Meter>().await.unwrap().is_none());
+
         ops.insert(Meter::new("meter1")).await.unwrap();
         ops.insert(Meter::new("meter2")).await.unwrap();
 
-        let last_meter = ops.get_last::<Meter>().await.unwrap();
+        let last_meter = ops.get_last::<Meter>().await.unwrap().unwrap();
         assert_eq!(last_meter.name, "meter2");
 
         ops.insert(Metric::new("metric1", last_meter.id, 100))
@@ -205,7 +782,7 @@ mod test {
             .await
             .unwrap();
 
-        let last_metric = ops.get_last::<Metric>().await.unwrap();
+        let last_metric = ops.get_last::<Metric>().await.unwrap().unwrap();
         assert_eq!(last_metric.name, "metric2");
         assert_eq!(last_metric.meter_id, last_meter.id);
         assert_eq!(last_metric.rate, 200);
@@ -218,7 +795,7 @@ mod test {
             .await
             .unwrap();
 
-        let last_metric_value = ops.get_last::<MetricValue>().await.unwrap();
+        let last_metric_value = ops.get_last::<MetricValue>().await.unwrap().unwrap();
         assert_eq!(last_metric_value.metric_id, last_metric.id);
         assert_eq!(last_metric_value.value, 5678);
         assert_eq!(last_metric_value.added, now);
@@ -245,6 +822,14 @@ mod test {
             .await
             .unwrap());
 
+        // names of digits are text too
+        ops.insert(Meter::new("007")).await.unwrap();
+        assert!(ops
+            .exists_by_name::<Meter>("007".to_string())
+            .await
+            .unwrap());
+        assert!(!ops.exists_by_name::<Meter>("7".to_string()).await.unwrap());
+
         let meters = ops.get_all::<Meter>().await.unwrap();
         ops.insert(Metric::new("metric1", meters[0].id, 100))
             .await
@@ -321,4 +906,420 @@ mod test {
             .await
             .unwrap());
     }
+
+    #[tokio::test]
+    async fn test_transaction_commit() {
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+
+        let meter_id = ops
+            .transaction(|transaction| {
+                let meter_id = transaction.insert(Meter::new("meter1"))?;
+                transaction.insert(Metric::new("metric1", meter_id, 100))?;
+                transaction.insert(Metric::new("metric2", meter_id, 200))?;
+                assert_eq!(transaction.get_all::<Metric>()?.len(), 2);
+                Ok(meter_id)
+            })
+            .await
+            .unwrap();
+
+        let meters = ops.get_all::<Meter>().await.unwrap();
+        assert_eq!(meters.len(), 1);
+        assert_eq!(meters[0].id, meter_id);
+
+        let metrics = ops.get_all::<Metric>().await.unwrap();
+        assert_eq!(metrics.len(), 2);
+        assert!(metrics.iter().all(|x| x.meter_id == meter_id));
+
+        ops.transaction(move |transaction| {
+            transaction.delete_where::<Metric>(Where::new(
+                "meter_id",
+                WhereExprOperator::Equal,
+                meter_id,
+            ))?;
+            transaction.delete_by_id::<Meter>(meter_id)
+        })
+        .await
+        .unwrap();
+
+        assert!(ops.get_all::<Meter>().await.unwrap().is_empty());
+        assert!(ops.get_all::<Metric>().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rollback() {
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+
+        ops.insert(Meter::new("meter1")).await.unwrap();
+
+        let result = ops
+            .transaction(|transaction| {
+                transaction.insert(Meter::new("meter2"))?;
+                transaction.delete_by_id::<Meter>(1)?;
+                Err::<(), Error>(Error::Other("something went wrong".into()))
+            })
+            .await;
+        assert!(result.is_err());
+
+        let meters = ops.get_all::<Meter>().await.unwrap();
+        assert_eq!(meters.len(), 1);
+        assert_eq!(meters[0].name, "meter1");
+    }
+
+    #[tokio::test]
+    async fn test_insert_many() {
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+
+        ops.insert(Meter::new("meter1")).await.unwrap();
+        let meter = ops.get_last::<Meter>().await.unwrap().unwrap();
+        ops.insert_many(vec![
+            Metric::new("metric1", meter.id, 100),
+            Metric::new("metric2", meter.id, 200),
+            Metric::new("metric3", meter.id, 300),
+        ])
+        .await
+        .unwrap();
+
+        let metrics = ops.get_all::<Metric>().await.unwrap();
+        assert_eq!(metrics.len(), 3);
+        assert_eq!(metrics[2].name, "metric3");
+        assert_eq!(metrics[2].rate, 300);
+
+        // the batch is audited row by row but reverted as a single action
+        let records = ops
+            .find::<AuditRecord>(Where::new("entity", WhereExprOperator::Equal, "metric"))
+            .await
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(records[2].after.as_deref().unwrap().contains("\"metric3\""));
+        let steps = ops
+            .find::<UndoStep>(Where::new("entity", WhereExprOperator::Equal, "metric"))
+            .await
+            .unwrap();
+        assert_eq!(steps.len(), 3);
+        assert!(steps.iter().all(|x| x.action_id == steps[0].action_id));
+        ops.undo(steps[0].action_id).await.unwrap();
+        assert!(ops.get_all::<Metric>().await.unwrap().is_empty());
+        assert_eq!(ops.get_all::<Meter>().await.unwrap().len(), 1);
+
+        ops.insert_many(vec![
+            Metric::new("metric1", meter.id, 100),
+            Metric::new("metric2", meter.id, 200),
+            Metric::new("metric3", meter.id, 300),
+        ])
+        .await
+        .unwrap();
+
+        // a failing entry rolls back the whole batch
+        ops.get_connection()
+            .call(|connection| {
+                Ok(connection.execute("CREATE UNIQUE INDEX metric_name ON metric (name)", ())?)
+            })
+            .await
+            .unwrap();
+        assert!(ops
+            .insert_many(vec![
+                Metric::new("metric4", meter.id, 400),
+                Metric::new("metric1", meter.id, 100),
+            ])
+            .await
+            .is_err());
+        assert_eq!(ops.get_all::<Metric>().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_expired_undo_actions() {
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+
+        ops.insert(Meter::new("meter1")).await.unwrap();
+        ops.insert(Meter::new("meter2")).await.unwrap();
+        let expired = ops.get_last::<UndoAction>().await.unwrap().unwrap();
+        ops.get_connection()
+            .call(move |connection| {
+                Ok(connection.execute(
+                    "UPDATE undo_action SET created = ?1",
+                    (expired.created - Duration::minutes(UNDO_MINUTES + 1),),
+                )?)
+            })
+            .await
+            .unwrap();
+
+        ops.insert(Meter::new("meter3")).await.unwrap();
+        // the newest expired action is kept, so that its id isn't reused
+        let actions = ops.get_all::<UndoAction>().await.unwrap();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].id, expired.id);
+        let steps = ops.get_all::<UndoStep>().await.unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].action_id, actions[1].id);
+
+        // bookkeeping is neither audited nor may be undone
+        ops.bookkeeping(|transaction| transaction.insert(Meter::new("meter4")))
+            .await
+            .unwrap();
+        assert_eq!(ops.get_all::<UndoAction>().await.unwrap().len(), 2);
+        assert_eq!(ops.get_all::<AuditRecord>().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_by_id() {
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+
+        ops.insert(Meter::new("meter1")).await.unwrap();
+        ops.insert(Meter::new("meter2")).await.unwrap();
+        let meters = ops.get_all::<Meter>().await.unwrap();
+
+        let meter = ops.get_by_id::<Meter>(meters[1].id).await.unwrap().unwrap();
+        assert_eq!(meter.id, meters[1].id);
+        assert_eq!(meter.name, "meter2");
+
+        assert!(ops.get_by_id::<Meter>(12345).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_find_and_count() {
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+
+        assert_eq!(ops.count::<Meter>().await.unwrap(), 0);
+
+        ops.insert(Meter::new("meter1")).await.unwrap();
+        ops.insert(Meter::new("meter2")).await.unwrap();
+        let meters = ops.get_all::<Meter>().await.unwrap();
+        assert_eq!(ops.count::<Meter>().await.unwrap(), 2);
+
+        ops.insert_many(vec![
+            Metric::new("metric1", meters[0].id, 100),
+            Metric::new("metric2", meters[1].id, 200),
+            Metric::new("metric3", meters[1].id, 300),
+        ])
+        .await
+        .unwrap();
+
+        let metrics = ops
+            .find::<Metric>(Where::new(
+                "meter_id",
+                WhereExprOperator::Equal,
+                meters[1].id,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].name, "metric2");
+        assert_eq!(metrics[1].name, "metric3");
+        assert_eq!(
+            ops.count_where::<Metric>(Where::new(
+                "meter_id",
+                WhereExprOperator::Equal,
+                meters[1].id
+            ))
+            .await
+            .unwrap(),
+            2
+        );
+
+        let metrics = ops
+            .find::<Metric>(
+                Where::new("meter_id", WhereExprOperator::Equal, meters[1].id).and(
+                    "name",
+                    WhereExprOperator::NotEqual,
+                    "metric2",
+                ),
+            )
+            .await
+            .unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].name, "metric3");
+
+        assert!(ops
+            .find::<Metric>(Where::new("name", WhereExprOperator::Equal, "metric4"))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_latest_values() {
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+
+        ops.insert(Meter::new("meter1")).await.unwrap();
+        ops.insert(Meter::new("meter2")).await.unwrap();
+        let meters = ops.get_all::<Meter>().await.unwrap();
+        ops.insert_many(vec![
+            Metric::new("metric1", meters[0].id, 100),
+            Metric::new("metric2", meters[0].id, 200),
+            Metric::new("metric3", meters[0].id, 300),
+            Metric::new("metric4", meters[1].id, 400),
+        ])
+        .await
+        .unwrap();
+        let metrics = ops.get_all::<Metric>().await.unwrap();
+
+        let earlier = NaiveDate::from_ymd_opt(2023, 11, 20)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let later = NaiveDate::from_ymd_opt(2023, 12, 20)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        ops.insert_many(vec![
+            MetricValue::new(metrics[0].id, 200, &later),
+            MetricValue::new(metrics[0].id, 100, &earlier),
+            MetricValue::new(metrics[1].id, 300, &earlier),
+            MetricValue::new(metrics[3].id, 400, &later),
+        ])
+        .await
+        .unwrap();
+
+        let latest = ops.get_latest_values(meters[0].id).await.unwrap();
+        assert_eq!(latest.len(), 3);
+        assert_eq!(latest[0].0.name, "metric1");
+        assert_eq!(latest[0].1.as_ref().unwrap().value, 200);
+        assert_eq!(latest[0].1.as_ref().unwrap().added, later);
+        assert_eq!(latest[1].0.name, "metric2");
+        assert_eq!(latest[1].1.as_ref().unwrap().value, 300);
+        assert_eq!(latest[1].1.as_ref().unwrap().added, earlier);
+        assert_eq!(latest[2].0.name, "metric3");
+        assert!(latest[2].1.is_none());
+
+        assert!(ops.get_latest_values(12345).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_find_page() {
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+
+        ops.insert_many(
+            (1..=25)
+                .map(|x| Meter::new(&std::format!("meter{}", x)))
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+        let page = ops
+            .find_page::<Meter>(None, "id", Order::Ascending, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 10);
+        assert_eq!(page[0].name, "meter1");
+        assert_eq!(page[9].name, "meter10");
+
+        let page = ops
+            .find_page::<Meter>(None, "id", Order::Ascending, 20, 10)
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 5);
+        assert_eq!(page[0].name, "meter21");
+
+        let page = ops
+            .find_page::<Meter>(None, "id", Order::Descending, 10, 3)
+            .await
+            .unwrap();
+        assert_eq!(
+            page.iter().map(|x| x.name.as_str()).collect::<Vec<&str>>(),
+            vec!["meter15", "meter14", "meter13"]
+        );
+
+        let page = ops
+            .find_page::<Meter>(
+                Some(Where::new("name", WhereExprOperator::NotEqual, "meter1")),
+                "id",
+                Order::Ascending,
+                0,
+                2,
+            )
+            .await
+            .unwrap();
+        assert_eq!(page[0].name, "meter2");
+        assert_eq!(page[1].name, "meter3");
+
+        assert!(ops
+            .find_page::<Meter>(None, "id", Order::Ascending, 30, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+
+        ops.insert(Meter::new("meter1")).await.unwrap();
+        ops.insert(Meter::new("meter2")).await.unwrap();
+
+        let mut meter = ops.get_last::<Meter>().await.unwrap().unwrap();
+        meter.name = "renamed".to_string();
+        ops.update(meter.clone()).await.unwrap();
+
+        let meters = ops.get_all::<Meter>().await.unwrap();
+        assert_eq!(meters[0].name, "meter1");
+        assert_eq!(meters[1].id, meter.id);
+        assert_eq!(meters[1].name, "renamed");
+
+        ops.transaction(|transaction| {
+            let mut meter = transaction
+                .find::<Meter>(Where::new("name", WhereExprOperator::Equal, "meter1"))?
+                .remove(0);
+            meter.name = "renamed1".to_string();
+            transaction.update(meter)
+        })
+        .await
+        .unwrap();
+        assert!(ops
+            .exists_by_name::<Meter>("renamed1".to_string())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("backup.sqlite");
+
+        let ops = Operations::in_memory().await.unwrap();
+        create_tables_if_do_not_exist(ops.get_connection()).await;
+        ops.insert(Meter::new("meter1")).await.unwrap();
+        ops.backup(path.clone()).await.unwrap();
+
+        ops.insert(Meter::new("meter2")).await.unwrap();
+        assert_eq!(ops.count::<Meter>().await.unwrap(), 2);
+
+        assert!(ops.restore(path.clone()).await.unwrap());
+        let meters = ops.get_all::<Meter>().await.unwrap();
+        assert_eq!(meters.len(), 1);
+        assert_eq!(meters[0].name, "meter1");
+
+        // restoring into a fresh file-backed database
+        let copy = Operations::open(directory.path().join("copy.sqlite"))
+            .await
+            .unwrap();
+        assert!(copy.restore(path.clone()).await.unwrap());
+        assert_eq!(copy.count::<Meter>().await.unwrap(), 1);
+
+        // not a database at all
+        let garbage = directory.path().join("garbage.sqlite");
+        std::fs::write(&garbage, "meter,metric").unwrap();
+        assert!(!ops.restore(garbage).await.unwrap());
+
+        // a database without the schema version
+        let empty = directory.path().join("empty.sqlite");
+        Operations::open(empty.clone())
+            .await
+            .unwrap()
+            .get_connection()
+            .call(|connection| Ok(connection.execute("CREATE TABLE x (id INTEGER)", ())?))
+            .await
+            .unwrap();
+        assert!(!ops.restore(empty).await.unwrap());
+        assert_eq!(ops.count::<Meter>().await.unwrap(), 1);
+    }
 }
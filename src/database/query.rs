@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use chrono::{NaiveDate, NaiveDateTime};
+
 use crate::database::structs::{FieldNames, TableName};
 
 pub struct Query;
@@ -8,10 +10,18 @@ pub struct Insert {
     query: String,
 }
 
+pub struct Update {
+    query: String,
+}
+
 #[derive(Clone)]
 pub enum WhereExprOperator {
     Equal,
     NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
 }
 
 #[derive(Clone)]
@@ -29,7 +39,7 @@ enum WhereElement {
 }
 
 #[derive(Clone)]
-struct Where {
+pub struct Where {
     elements: Vec<WhereElement>,
 }
 
@@ -50,17 +60,24 @@ struct Limit {
     limit_to: usize,
 }
 
+#[derive(Clone)]
+struct Offset {
+    skip: usize,
+}
+
 #[derive(Clone)]
 enum SelectOrDeleteMark {
     Select,
+    Count,
     Delete,
 }
 
 #[derive(Clone)]
 pub struct SelectOrDelete {
     query: String,
-    order_info: Option<OrderBy>,
+    order_info: Vec<OrderBy>,
     limit_info: Option<Limit>,
+    offset_info: Option<Offset>,
     where_info: Option<Where>,
 }
 
@@ -68,24 +85,47 @@ pub struct WhereActions {
     select: SelectOrDelete,
 }
 
+impl Where {
+    pub fn new<T: SqlValue>(field: &str, operator: WhereExprOperator, value: T) -> Self {
+        Where {
+            elements: vec![WhereElement::None(WhereExpr::new(field, operator, value))],
+        }
+    }
+
+    fn new_conjunction(&self, element: WhereElement) -> Self {
+        let mut elements = self.elements.clone();
+        elements.push(element);
+        Where { elements }
+    }
+
+    pub fn or<T: SqlValue>(&self, field: &str, operator: WhereExprOperator, value: T) -> Self {
+        self.new_conjunction(WhereElement::Or(WhereExpr::new(field, operator, value)))
+    }
+
+    pub fn and<T: SqlValue>(&self, field: &str, operator: WhereExprOperator, value: T) -> Self {
+        self.new_conjunction(WhereElement::And(WhereExpr::new(field, operator, value)))
+    }
+}
+
+impl WhereExpr {
+    fn new<T: SqlValue>(field: &str, operator: WhereExprOperator, value: T) -> Self {
+        WhereExpr {
+            field: field.to_string(),
+            operator,
+            value: value.to_sql(),
+        }
+    }
+}
+
 impl WhereActions {
-    fn start_where<T: Display>(
+    fn start_where<T: SqlValue>(
         select: &SelectOrDelete,
         field: &str,
         operator: WhereExprOperator,
         value: T,
     ) -> Self {
         WhereActions {
-            select: SelectOrDelete {
-                where_info: Some(Where {
-                    elements: vec![WhereElement::None(WhereExpr {
-                        field: field.to_string(),
-                        operator,
-                        value: value.to_string(),
-                    })],
-                }),
-                ..select.clone()
-            },
+            select: select.filter(Where::new(field, operator, value)),
         }
     }
 
@@ -93,37 +133,23 @@ impl WhereActions {
         self.select.clone()
     }
 
-    fn new_conjunction(&self, element: WhereElement) -> Self {
-        let mut new_elements = match &self.select.where_info {
-            Some(where_info) => where_info.elements.clone(),
+    fn update_where(&self, update: impl FnOnce(&Where) -> Where) -> Self {
+        let where_info = match &self.select.where_info {
+            Some(where_info) => update(where_info),
             None => panic!("where_info cannot be empty at this point"),
         };
-        new_elements.push(element);
 
         WhereActions {
-            select: SelectOrDelete {
-                where_info: Some(Where {
-                    elements: new_elements,
-                }),
-                ..self.select.clone()
-            },
+            select: self.select.filter(where_info),
         }
     }
 
-    pub fn or<T: Display>(&self, field: &str, operator: WhereExprOperator, value: T) -> Self {
-        self.new_conjunction(WhereElement::Or(WhereExpr {
-            field: field.to_string(),
-            operator,
-            value: value.to_string(),
-        }))
+    pub fn or<T: SqlValue>(&self, field: &str, operator: WhereExprOperator, value: T) -> Self {
+        self.update_where(|where_info| where_info.or(field, operator, value))
     }
 
-    pub fn and<T: Display>(&self, field: &str, operator: WhereExprOperator, value: T) -> Self {
-        self.new_conjunction(WhereElement::And(WhereExpr {
-            field: field.to_string(),
-            operator,
-            value: value.to_string(),
-        }))
+    pub fn and<T: SqlValue>(&self, field: &str, operator: WhereExprOperator, value: T) -> Self {
+        self.update_where(|where_info| where_info.and(field, operator, value))
     }
 }
 
@@ -151,6 +177,29 @@ impl Insert {
     }
 }
 
+impl Update {
+    fn new<T: TableName + FieldNames>() -> Self {
+        let fields = T::get_field_names();
+        let assignments = fields
+            .iter()
+            .skip(1)
+            .enumerate()
+            .map(|(index, field)| std::format!("{} = ?{}", field, index + 1))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        Update {
+            query: std::format!(
+                "UPDATE {} SET {} WHERE {} = ?{}",
+                T::TABLE_NAME,
+                assignments,
+                fields[0],
+                fields.len()
+            ),
+        }
+    }
+}
+
 impl SelectOrDelete {
     fn new<T: TableName>(which: SelectOrDeleteMark) -> Self {
         SelectOrDelete {
@@ -158,17 +207,19 @@ impl SelectOrDelete {
                 "{} FROM {}",
                 match which {
                     SelectOrDeleteMark::Select => "SELECT *",
+                    SelectOrDeleteMark::Count => "SELECT COUNT(*)",
                     SelectOrDeleteMark::Delete => "DELETE",
                 },
                 T::TABLE_NAME
             ),
-            order_info: None,
+            order_info: Vec::new(),
             limit_info: None,
+            offset_info: None,
             where_info: None,
         }
     }
 
-    pub fn where_<T: Display>(
+    pub fn where_<T: SqlValue>(
         &self,
         field: &str,
         operator: WhereExprOperator,
@@ -177,6 +228,13 @@ impl SelectOrDelete {
         WhereActions::start_where(&self.clone(), field, operator, value)
     }
 
+    pub fn filter(&self, where_info: Where) -> Self {
+        SelectOrDelete {
+            where_info: Some(where_info),
+            ..self.clone()
+        }
+    }
+
     pub fn limit(&self, limit_to: usize) -> Self {
         SelectOrDelete {
             limit_info: Some(Limit { limit_to }),
@@ -184,12 +242,23 @@ impl SelectOrDelete {
         }
     }
 
+    /// Only meaningful together with `limit`.
+    pub fn offset(&self, skip: usize) -> Self {
+        SelectOrDelete {
+            offset_info: Some(Offset { skip }),
+            ..self.clone()
+        }
+    }
+
+    /// Sorts by `field`, the rows equal in the fields given before.
     pub fn order_by(&self, field: &str, order: Order) -> Self {
+        let mut order_info = self.order_info.clone();
+        order_info.push(OrderBy {
+            field: field.to_string(),
+            order,
+        });
         SelectOrDelete {
-            order_info: Some(OrderBy {
-                field: field.to_string(),
-                order,
-            }),
+            order_info,
             ..self.clone()
         }
     }
@@ -200,10 +269,18 @@ impl Query {
         Insert::new::<T>()
     }
 
+    pub fn update<T: TableName + FieldNames>() -> Update {
+        Update::new::<T>()
+    }
+
     pub fn select<T: TableName>() -> SelectOrDelete {
         SelectOrDelete::new::<T>(SelectOrDeleteMark::Select)
     }
 
+    pub fn count<T: TableName>() -> SelectOrDelete {
+        SelectOrDelete::new::<T>(SelectOrDeleteMark::Count)
+    }
+
     pub fn delete<T: TableName>() -> SelectOrDelete {
         SelectOrDelete::new::<T>(SelectOrDeleteMark::Delete)
     }
@@ -216,6 +293,48 @@ fn option_to_string<T: Display>(value: &Option<T>) -> String {
     }
 }
 
+/// A value compared with a field, written as an SQL literal of its own type:
+/// numbers as they are and text quoted, even if it consists of digits.
+pub trait SqlValue {
+    fn to_sql(&self) -> String;
+}
+
+macro_rules! sql_number {
+    ($($type:ty),*) => {
+        $(impl SqlValue for $type {
+            fn to_sql(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+sql_number!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+macro_rules! sql_text {
+    ($($type:ty),*) => {
+        $(impl SqlValue for $type {
+            fn to_sql(&self) -> String {
+                std::format!("'{}'", self.to_string().replace('\'', "''"))
+            }
+        })*
+    };
+}
+
+sql_text!(str, String, NaiveDate, NaiveDateTime);
+
+impl SqlValue for bool {
+    fn to_sql(&self) -> String {
+        (*self as u32).to_string()
+    }
+}
+
+impl<T: SqlValue + ?Sized> SqlValue for &T {
+    fn to_sql(&self) -> String {
+        (**self).to_sql()
+    }
+}
+
 impl Display for WhereExprOperator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -224,6 +343,10 @@ impl Display for WhereExprOperator {
             match *self {
                 WhereExprOperator::Equal => "=",
                 WhereExprOperator::NotEqual => "!=",
+                WhereExprOperator::Less => "<",
+                WhereExprOperator::LessOrEqual => "<=",
+                WhereExprOperator::Greater => ">",
+                WhereExprOperator::GreaterOrEqual => ">=",
             }
         )
     }
@@ -231,16 +354,7 @@ impl Display for WhereExprOperator {
 
 impl Display for WhereExpr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{} {} {}",
-            self.field,
-            self.operator,
-            match &self.value.chars().all(|x| x.is_numeric()) {
-                true => self.value.to_string(),
-                false => std::format!("'{}'", self.value),
-            }
-        )
+        write!(f, "{} {} {}", self.field, self.operator, self.value)
     }
 }
 
@@ -278,7 +392,7 @@ impl Display for OrderBy {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            " ORDER BY {}{}",
+            "{}{}",
             self.field,
             match self.order {
                 Order::Ascending => "",
@@ -294,21 +408,44 @@ impl Display for Limit {
     }
 }
 
+impl Display for Offset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, " OFFSET {}", self.skip)
+    }
+}
+
 impl Display for Insert {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.query)
     }
 }
 
+impl Display for Update {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.query)
+    }
+}
+
 impl Display for SelectOrDelete {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{}{}{}{}",
+            "{}{}{}{}{}",
             self.query,
             option_to_string(&self.where_info),
-            option_to_string(&self.order_info),
-            option_to_string(&self.limit_info)
+            match self.order_info.is_empty() {
+                true => "".to_string(),
+                false => std::format!(
+                    " ORDER BY {}",
+                    self.order_info
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            },
+            option_to_string(&self.limit_info),
+            option_to_string(&self.offset_info)
         )
     }
 }
@@ -316,14 +453,26 @@ impl Display for SelectOrDelete {
 #[cfg(test)]
 mod test {
     use crate::database::{
-        query::{Order, Query, WhereExprOperator},
+        query::{Order, Query, Where, WhereExprOperator},
         structs::{Meter, Metric, MetricValue},
     };
     #[test]
     fn test_insert() {
         assert_eq!(
             Query::insert::<Meter>().to_string(),
-            "INSERT INTO meter (name) VALUES (?1)"
+            "INSERT INTO meter (name,archived) VALUES (?1,?2)"
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        assert_eq!(
+            Query::update::<Meter>().to_string(),
+            "UPDATE meter SET name = ?1, archived = ?2 WHERE id = ?3"
+        );
+        assert_eq!(
+            Query::update::<MetricValue>().to_string(),
+            "UPDATE metric_value SET metric_id = ?1, value = ?2, added = ?3 WHERE id = ?4"
         );
     }
 
@@ -360,6 +509,21 @@ mod test {
                 .to_string(),
             "SELECT * FROM metric_value ORDER BY id DESC LIMIT 456"
         );
+        assert_eq!(
+            Query::select::<Meter>()
+                .offset(20)
+                .limit(10)
+                .order_by("id", Order::Ascending)
+                .to_string(),
+            "SELECT * FROM meter ORDER BY id LIMIT 10 OFFSET 20"
+        );
+        assert_eq!(
+            Query::select::<MetricValue>()
+                .order_by("added", Order::Descending)
+                .order_by("id", Order::Descending)
+                .to_string(),
+            "SELECT * FROM metric_value ORDER BY added DESC, id DESC"
+        );
     }
 
     #[test]
@@ -440,6 +604,15 @@ mod test {
                 .to_string(),
             "SELECT * FROM meter WHERE id = 123 AND name != 'some_name' OR surname = 'some_surname'"
         );
+        assert_eq!(
+            Query::select::<MetricValue>()
+                .where_("value", WhereExprOperator::Greater, 10)
+                .and("value", WhereExprOperator::LessOrEqual, 20)
+                .or("added", WhereExprOperator::GreaterOrEqual, "2023-12-15 00:00:00")
+                .or("id", WhereExprOperator::Less, 5)
+                .to_string(),
+            "SELECT * FROM metric_value WHERE value > 10 AND value <= 20 OR added >= '2023-12-15 00:00:00' OR id < 5"
+        );
     }
 
     #[test]
@@ -536,4 +709,74 @@ mod test {
             "DELETE FROM metric_value WHERE id = 123 AND name != 'some_name' OR surname = 'some_surname' ORDER BY id DESC LIMIT 456"
         );
     }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(
+            Query::count::<Meter>().to_string(),
+            "SELECT COUNT(*) FROM meter"
+        );
+        assert_eq!(
+            Query::count::<Metric>()
+                .where_("meter_id", WhereExprOperator::Equal, 123)
+                .to_string(),
+            "SELECT COUNT(*) FROM metric WHERE meter_id = 123"
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let where_info = Where::new("id", WhereExprOperator::Equal, 123)
+            .and("name", WhereExprOperator::NotEqual, "some_name")
+            .or("surname", WhereExprOperator::Equal, "some_surname");
+        assert_eq!(
+            Query::select::<Meter>()
+                .filter(where_info.clone())
+                .order_by("id", Order::Descending)
+                .to_string(),
+            "SELECT * FROM meter WHERE id = 123 AND name != 'some_name' OR surname = 'some_surname' ORDER BY id DESC"
+        );
+        assert_eq!(
+            Query::delete::<Meter>().filter(where_info).to_string(),
+            "DELETE FROM meter WHERE id = 123 AND name != 'some_name' OR surname = 'some_surname'"
+        );
+    }
+
+    #[test]
+    fn test_where_quotes_are_escaped() {
+        assert_eq!(
+            Query::select::<Meter>()
+                .where_("name", WhereExprOperator::Equal, "it's")
+                .to_string(),
+            "SELECT * FROM meter WHERE name = 'it''s'"
+        );
+    }
+
+    #[test]
+    fn test_where_values_by_type() {
+        assert_eq!(
+            Query::select::<Meter>()
+                .where_("name", WhereExprOperator::Equal, "")
+                .to_string(),
+            "SELECT * FROM meter WHERE name = ''"
+        );
+        assert_eq!(
+            Query::select::<Meter>()
+                .where_("name", WhereExprOperator::Equal, "007")
+                .to_string(),
+            "SELECT * FROM meter WHERE name = '007'"
+        );
+        assert_eq!(
+            Query::select::<Meter>()
+                .where_("name", WhereExprOperator::Equal, "٣".to_string())
+                .to_string(),
+            "SELECT * FROM meter WHERE name = '٣'"
+        );
+        assert_eq!(
+            Query::select::<Meter>()
+                .where_("id", WhereExprOperator::Equal, 30)
+                .to_string(),
+            "SELECT * FROM meter WHERE id = 30"
+        );
+    }
 }
@@ -1,5 +1,5 @@
-use chrono::NaiveDateTime;
-use meters_derive::{FieldNames, FromRow, InsertValues, TableName};
+use chrono::{NaiveDate, NaiveDateTime};
+use meters_derive::{FieldNames, FromRow, InsertValues, TableName, UpdateValues};
 use rusqlite::{Params, Row};
 
 const NON_EXISTENT_INDEX: u32 = 0;
@@ -18,17 +18,33 @@ pub trait InsertValues {
     fn get_insert_values(&self) -> Self::Values;
 }
 
-pub trait FromRow {
-    fn from_row(row: &Row) -> Self;
+/// Values of all fields but the id, followed by the id.
+pub trait UpdateValues {
+    type Values: Params;
+
+    fn get_update_values(&self) -> Self::Values;
+
+    fn get_id(&self) -> u32;
+}
+
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Self {
+        Self::from_row_at(row, 0)
+    }
+
+    /// Reads the fields starting from column `offset`, used for joined rows.
+    fn from_row_at(row: &Row, offset: usize) -> Self;
 }
 
-#[derive(Debug, TableName, FieldNames, InsertValues, FromRow, Clone)]
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
 pub struct Meter {
     pub id: u32,
     pub name: String,
+    /// Archived meters are hidden from the menus, but keep their history.
+    pub archived: bool,
 }
 
-#[derive(Debug, TableName, FieldNames, InsertValues, FromRow, Clone)]
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
 pub struct Metric {
     pub id: u32,
     pub name: String,
@@ -36,7 +52,7 @@ pub struct Metric {
     pub rate: u32,
 }
 
-#[derive(Debug, TableName, FieldNames, InsertValues, FromRow, Clone)]
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
 pub struct MetricValue {
     pub id: u32,
     pub metric_id: u32,
@@ -44,11 +60,108 @@ pub struct MetricValue {
     pub added: NaiveDateTime,
 }
 
+/// When to remind a chat to submit readings: every month between `first_day`
+/// and `last_day`, not earlier than `hour`, in the language of the user who
+/// set it. Reminders set before the language was kept have none.
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
+pub struct Reminder {
+    pub id: u32,
+    pub chat_id: i64,
+    pub first_day: u32,
+    pub last_day: u32,
+    pub hour: u32,
+    pub last_sent: Option<NaiveDate>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
+pub struct UserSettings {
+    pub id: u32,
+    pub user_id: i64,
+    pub language: String,
+}
+
+/// How many times the daily consumption of a metric may exceed its recent
+/// average before the chat is warned. No factor means no warnings.
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
+pub struct MetricAlert {
+    pub id: u32,
+    pub metric_id: u32,
+    pub factor: Option<f64>,
+}
+
+/// Number of digits of a mechanical counter, which starts from zero again
+/// after the largest number it can show.
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
+pub struct MetricCapacity {
+    pub id: u32,
+    pub metric_id: u32,
+    pub digits: u32,
+}
+
+/// Replacement of the counter of a metric, where the old one has shown
+/// `old_value` last and the new one has started from `new_value`.
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
+pub struct MetricReplacement {
+    pub id: u32,
+    pub metric_id: u32,
+    pub old_value: u32,
+    pub new_value: u32,
+    pub replaced: NaiveDateTime,
+}
+
+/// Photo of the counter taken when a value was submitted: its Telegram file
+/// id and the path of a local copy, if photos are kept on disk.
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
+pub struct ReadingPhoto {
+    pub id: u32,
+    pub metric_value_id: u32,
+    pub metric_id: u32,
+    pub file_id: String,
+    pub path: Option<String>,
+}
+
+/// A change of a row of any table: the state of the row before and after it,
+/// and the user who has made it, if it wasn't the bot itself.
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
+pub struct AuditRecord {
+    pub id: u32,
+    pub user_id: Option<i64>,
+    pub changed: NaiveDateTime,
+    pub entity: String,
+    pub entity_id: u32,
+    pub action: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Changes made at once, which the user who made them may revert.
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
+pub struct UndoAction {
+    pub id: u32,
+    pub user_id: Option<i64>,
+    pub created: NaiveDateTime,
+    pub undone: bool,
+}
+
+/// How to revert a change of a row of the action: `row` holds the values of
+/// all columns to put back as a JSON array, no row means that the row has been
+/// inserted and is to be deleted.
+#[derive(Debug, TableName, FieldNames, InsertValues, UpdateValues, FromRow, Clone)]
+pub struct UndoStep {
+    pub id: u32,
+    pub action_id: u32,
+    pub entity: String,
+    pub entity_id: u32,
+    pub row: Option<String>,
+}
+
 impl Meter {
     pub fn new(name: &str) -> Self {
         Meter {
             id: NON_EXISTENT_INDEX,
             name: name.to_string(),
+            archived: false,
         }
     }
 }
@@ -75,10 +188,132 @@ impl MetricValue {
     }
 }
 
+impl Reminder {
+    pub fn new(chat_id: i64, first_day: u32, last_day: u32, hour: u32, language: &str) -> Self {
+        Reminder {
+            id: NON_EXISTENT_INDEX,
+            chat_id,
+            first_day,
+            last_day,
+            hour,
+            last_sent: None,
+            language: Some(language.to_string()),
+        }
+    }
+}
+
+impl UserSettings {
+    pub fn new(user_id: i64, language: &str) -> Self {
+        UserSettings {
+            id: NON_EXISTENT_INDEX,
+            user_id,
+            language: language.to_string(),
+        }
+    }
+}
+
+impl MetricAlert {
+    pub fn new(metric_id: u32, factor: Option<f64>) -> Self {
+        MetricAlert {
+            id: NON_EXISTENT_INDEX,
+            metric_id,
+            factor,
+        }
+    }
+}
+
+impl MetricCapacity {
+    pub fn new(metric_id: u32, digits: u32) -> Self {
+        MetricCapacity {
+            id: NON_EXISTENT_INDEX,
+            metric_id,
+            digits,
+        }
+    }
+}
+
+impl MetricReplacement {
+    pub fn new(metric_id: u32, old_value: u32, new_value: u32, replaced: &NaiveDateTime) -> Self {
+        MetricReplacement {
+            id: NON_EXISTENT_INDEX,
+            metric_id,
+            old_value,
+            new_value,
+            replaced: *replaced,
+        }
+    }
+}
+
+impl ReadingPhoto {
+    /// A photo not linked to a value yet, which happens when it is submitted.
+    pub fn new(file_id: &str, path: Option<String>) -> Self {
+        ReadingPhoto {
+            id: NON_EXISTENT_INDEX,
+            metric_value_id: NON_EXISTENT_INDEX,
+            metric_id: NON_EXISTENT_INDEX,
+            file_id: file_id.to_string(),
+            path,
+        }
+    }
+}
+
+impl AuditRecord {
+    pub const INSERT: &'static str = "insert";
+    pub const UPDATE: &'static str = "update";
+    pub const DELETE: &'static str = "delete";
+    pub const UNDO: &'static str = "undo";
+
+    pub fn new(
+        user_id: Option<i64>,
+        changed: &NaiveDateTime,
+        entity: &str,
+        entity_id: u32,
+        action: &str,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> Self {
+        AuditRecord {
+            id: NON_EXISTENT_INDEX,
+            user_id,
+            changed: *changed,
+            entity: entity.to_string(),
+            entity_id,
+            action: action.to_string(),
+            before,
+            after,
+        }
+    }
+}
+
+impl UndoAction {
+    pub fn new(user_id: Option<i64>, created: &NaiveDateTime) -> Self {
+        UndoAction {
+            id: NON_EXISTENT_INDEX,
+            user_id,
+            created: *created,
+            undone: false,
+        }
+    }
+}
+
+impl UndoStep {
+    pub fn new(action_id: u32, entity: &str, entity_id: u32, row: Option<String>) -> Self {
+        UndoStep {
+            id: NON_EXISTENT_INDEX,
+            action_id,
+            entity: entity.to_string(),
+            entity_id,
+            row,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::database::structs::{
-        FieldNames, InsertValues, Meter, Metric, MetricValue, TableName,
+        AuditRecord, FieldNames, InsertValues, Meter, Metric, MetricAlert, MetricCapacity,
+        MetricReplacement, MetricValue, ReadingPhoto, Reminder, TableName, UndoAction, UndoStep,
+        UpdateValues, UserSettings,
     };
     use chrono::Local;
 
@@ -87,11 +322,20 @@ mod test {
         assert_eq!(Meter::TABLE_NAME, "meter");
         assert_eq!(Metric::TABLE_NAME, "metric");
         assert_eq!(MetricValue::TABLE_NAME, "metric_value");
+        assert_eq!(Reminder::TABLE_NAME, "reminder");
+        assert_eq!(UserSettings::TABLE_NAME, "user_settings");
+        assert_eq!(MetricAlert::TABLE_NAME, "metric_alert");
+        assert_eq!(MetricCapacity::TABLE_NAME, "metric_capacity");
+        assert_eq!(MetricReplacement::TABLE_NAME, "metric_replacement");
+        assert_eq!(ReadingPhoto::TABLE_NAME, "reading_photo");
+        assert_eq!(AuditRecord::TABLE_NAME, "audit_record");
+        assert_eq!(UndoAction::TABLE_NAME, "undo_action");
+        assert_eq!(UndoStep::TABLE_NAME, "undo_step");
     }
 
     #[test]
     fn test_field_names() {
-        assert_eq!(Meter::get_field_names(), vec!["id", "name"]);
+        assert_eq!(Meter::get_field_names(), vec!["id", "name", "archived"]);
         assert_eq!(
             Metric::get_field_names(),
             vec!["id", "name", "meter_id", "rate"]
@@ -100,13 +344,25 @@ mod test {
             MetricValue::get_field_names(),
             vec!["id", "metric_id", "value", "added"]
         );
+        assert_eq!(
+            Reminder::get_field_names(),
+            vec![
+                "id",
+                "chat_id",
+                "first_day",
+                "last_day",
+                "hour",
+                "last_sent",
+                "language"
+            ]
+        );
     }
 
     #[test]
     fn test_insert_values() {
         assert_eq!(
             Meter::new("meter1").get_insert_values(),
-            ("meter1".to_string(),)
+            ("meter1".to_string(), false)
         );
         assert_eq!(
             Metric::new("metric1", 123, 456).get_insert_values(),
@@ -119,4 +375,19 @@ mod test {
             (123, 456, now)
         );
     }
+
+    #[test]
+    fn test_update_values() {
+        let mut meter = Meter::new("meter1");
+        meter.id = 12;
+        assert_eq!(meter.get_update_values(), ("meter1".to_string(), false, 12));
+
+        let mut reminder = Reminder::new(-100, 15, 25, 10, "uk");
+        reminder.id = 3;
+        assert_eq!(
+            reminder.get_update_values(),
+            (-100, 15, 25, 10, None, Some("uk".to_string()), 3)
+        );
+        assert_eq!(reminder.get_id(), 3);
+    }
 }
@@ -1 +1,270 @@
-pub const LANG: &str = "en";
+use rust_i18n::t;
+use std::ops::Deref;
+
+pub const DEFAULT_LANG: &str = "en";
+pub const LANGS: [&str; 3] = ["en", "ru", "uk"];
+
+/// Language the messages for a particular user are rendered in, always one of
+/// [`LANGS`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Locale(String);
+
+impl Locale {
+    /// Picks the supported language matching `code`, which may be a Telegram
+    /// `language_code` like `en-US`, falling back to [`DEFAULT_LANG`].
+    pub fn new(code: &str) -> Self {
+        let language = code.split(['-', '_']).next().unwrap_or("").to_lowercase();
+        Locale(
+            LANGS
+                .into_iter()
+                .find(|x| *x == language)
+                .unwrap_or(DEFAULT_LANG)
+                .to_string(),
+        )
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale(DEFAULT_LANG.to_string())
+    }
+}
+
+impl Deref for Locale {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// CLDR plural category of the integer `count` in the language of `locale`.
+pub fn plural_category(locale: &str, count: usize) -> &'static str {
+    match locale {
+        "ru" | "uk" => {
+            let (last_digit, last_two_digits) = (count % 10, count % 100);
+            if last_digit == 1 && last_two_digits != 11 {
+                "one"
+            } else if (2..=4).contains(&last_digit) && !(12..=14).contains(&last_two_digits) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        _ => match count {
+            1 => "one",
+            _ => "other",
+        },
+    }
+}
+
+/// Key of the plural form of `key` for `count`, e.g. `message.found-meters.few`.
+/// Every locale defines the forms its plural rule produces.
+pub fn plural_key(key: &str, count: usize, locale: &str) -> String {
+    std::format!("{}.{}", key, plural_category(locale, count))
+}
+
+/// Joins `items` the way the language of `locale` does: "a, b and c".
+pub fn format_list(items: &[String], locale: &str) -> String {
+    match items {
+        [] => "".to_string(),
+        [item] => item.clone(),
+        [rest @ .., last] => std::format!(
+            "{}{}{}",
+            rest.join(&t!("list.separator", locale = locale)),
+            t!("list.last-separator", locale = locale),
+            last
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_list, plural_category, plural_key, Locale, LANGS};
+    use rust_i18n::t;
+    use std::path::Path;
+
+    const KEY_PREFIXES: [&str; 6] = [
+        "message.",
+        "button.",
+        "error.",
+        "table.",
+        "list.",
+        "language.",
+    ];
+
+    /// Contents of the source files under `path`.
+    fn sources(path: &Path, sources: &mut Vec<String>) {
+        for entry in std::fs::read_dir(path).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                self::sources(&path, sources);
+                continue;
+            }
+            sources.push(std::fs::read_to_string(&path).unwrap());
+        }
+    }
+
+    fn is_key(literal: &str) -> bool {
+        KEY_PREFIXES
+            .iter()
+            .any(|x| literal.starts_with(x) && literal.len() > x.len())
+            && literal
+                .chars()
+                .all(|x| x.is_ascii_lowercase() || x.is_ascii_digit() || x == '.' || x == '-')
+    }
+
+    /// String literals of the sources that look like keys.
+    fn referenced_keys(sources: &[String]) -> Vec<String> {
+        let mut keys = Vec::new();
+        for source in sources {
+            // every other piece between quotes is a literal
+            for literal in source.split('"').skip(1).step_by(2) {
+                if is_key(literal) {
+                    keys.push(literal.to_string());
+                }
+            }
+        }
+        keys
+    }
+
+    /// Keys given as literals to calls of `function`, like `plural_key(`, which
+    /// are completed or filled at runtime.
+    fn key_arguments(sources: &[String], function: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        for source in sources {
+            for call in source.split(function).skip(1) {
+                match call.strip_prefix('"').and_then(|x| x.split('"').next()) {
+                    Some(key) if is_key(key) => keys.push(key.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    #[test]
+    fn test_locale() {
+        assert_eq!(&*Locale::new("ru"), "ru");
+        assert_eq!(&*Locale::new("uk"), "uk");
+        assert_eq!(&*Locale::new("en-US"), "en");
+        assert_eq!(&*Locale::new("UK_ua"), "uk");
+        assert_eq!(&*Locale::new("de"), "en");
+        assert_eq!(&*Locale::new(""), "en");
+        assert_eq!(Locale::default(), Locale::new("en"));
+    }
+
+    #[test]
+    fn test_languages_are_translated() {
+        let available = rust_i18n::available_locales!();
+        for language in LANGS {
+            assert!(available.contains(&language));
+        }
+        assert_eq!(t!("button.done", locale = "ru"), "Готово");
+        assert_eq!(t!("button.back", locale = "uk"), "Повернутися");
+        // untranslated languages fall back to English
+        assert_eq!(t!("button.done", locale = "de"), "Done");
+    }
+
+    #[test]
+    fn test_plural_category() {
+        assert_eq!(plural_category("en", 0), "other");
+        assert_eq!(plural_category("en", 1), "one");
+        assert_eq!(plural_category("en", 2), "other");
+        assert_eq!(plural_category("en", 21), "other");
+
+        for locale in ["ru", "uk"] {
+            for count in [1, 21, 101, 1001] {
+                assert_eq!(plural_category(locale, count), "one");
+            }
+            for count in [2, 3, 4, 22, 34, 102] {
+                assert_eq!(plural_category(locale, count), "few");
+            }
+            for count in [0, 5, 11, 12, 14, 19, 20, 25, 111, 112] {
+                assert_eq!(plural_category(locale, count), "many");
+            }
+        }
+    }
+
+    #[test]
+    fn test_plural_forms() {
+        let found = |count: usize, locale: &str| {
+            t!(
+                &plural_key("message.found-meters", count, locale),
+                locale = locale,
+                count = count,
+                meters = "..."
+            )
+        };
+        assert_eq!(found(1, "en"), "Found 1 meter: ...");
+        assert_eq!(found(3, "en"), "Found 3 meters: ...");
+        assert_eq!(found(1, "ru"), "Найден 1 счётчик: ...");
+        assert_eq!(found(3, "ru"), "Найдено 3 счётчика: ...");
+        assert_eq!(found(5, "ru"), "Найдено 5 счётчиков: ...");
+        assert_eq!(found(21, "uk"), "Знайдено 21 лічильник: ...");
+        assert_eq!(found(22, "uk"), "Знайдено 22 лічильники: ...");
+        assert_eq!(found(11, "uk"), "Знайдено 11 лічильників: ...");
+    }
+
+    #[test]
+    fn test_format_list() {
+        let items = ["a", "b", "c"].map(|x| x.to_string());
+        assert_eq!(format_list(&items[..0], "en"), "");
+        assert_eq!(format_list(&items[..1], "en"), "a");
+        assert_eq!(format_list(&items[..2], "en"), "a and b");
+        assert_eq!(format_list(&items, "en"), "a, b and c");
+        assert_eq!(format_list(&items, "ru"), "a, b и c");
+        assert_eq!(format_list(&items, "uk"), "a, b і c");
+    }
+
+    #[test]
+    fn test_all_keys_are_translated() {
+        let mut sources = Vec::new();
+        self::sources(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut sources,
+        );
+        let keys = referenced_keys(&sources);
+        assert!(keys.contains(&"message.start".to_string()));
+        let plural_keys = key_arguments(&sources, "plural_key(");
+        assert!(plural_keys.contains(&"message.found-meters".to_string()));
+        let error_keys = key_arguments(&sources, "error_message(");
+        assert!(error_keys.contains(&"error.import".to_string()));
+
+        let translation =
+            |locale: &str, key: &str| crate::_RUST_I18N_BACKEND.translate(locale, key);
+        let mut missing = Vec::new();
+        for locale in LANGS {
+            // a message with plural forms has every form of the language
+            let mut categories = (0..200)
+                .map(|x| plural_category(locale, x))
+                .collect::<Vec<&str>>();
+            categories.sort();
+            categories.dedup();
+            for key in &plural_keys {
+                for category in &categories {
+                    let form = std::format!("{}.{}", key, category);
+                    if translation(locale, &form).is_none() {
+                        missing.push(std::format!("{}.{}", locale, form));
+                    }
+                }
+            }
+            for key in keys.iter().filter(|x| !plural_keys.contains(x)) {
+                if translation(locale, key).is_none() {
+                    missing.push(std::format!("{}.{}", locale, key));
+                }
+            }
+            // the reason of the error is put into the message
+            for key in &error_keys {
+                if !translation(locale, key).is_some_and(|x| x.contains("%{reason}")) {
+                    missing.push(std::format!("{}.{} with %{{reason}}", locale, key));
+                }
+            }
+        }
+        missing.sort();
+        missing.dedup();
+        assert!(missing.is_empty(), "Missing translations: {:?}", missing);
+    }
+}
@@ -0,0 +1,11 @@
+mod api;
+mod bot;
+mod commands;
+pub mod database;
+mod lang;
+
+pub use api::router;
+pub use bot::start;
+pub use commands::{Commands, ImportRow};
+
+rust_i18n::i18n!("locales", fallback = "en");
@@ -0,0 +1,191 @@
+use axum::{
+    body::Body,
+    http::{header::AUTHORIZATION, header::CONTENT_TYPE, Request, StatusCode},
+    Router,
+};
+use chrono::NaiveDate;
+use meters::database::create::create_tables_if_do_not_exist;
+use meters::{router, Commands, ImportRow};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+const TOKEN: &str = "token";
+
+/// Router over a database with a meter of two metrics read twice.
+async fn test_router() -> (Router, Arc<Commands>) {
+    let commands = Arc::new(Commands::in_memory().await.unwrap());
+    create_tables_if_do_not_exist(commands.get_connection()).await;
+    commands.add_meter("power").await.unwrap();
+    let meter = commands.list_meters().await.unwrap().remove(0);
+    let row = |metric: &str, value, rate, month| ImportRow {
+        line: 0,
+        meter: "power".to_string(),
+        metric: metric.to_string(),
+        value,
+        rate,
+        added: NaiveDate::from_ymd_opt(2023, month, 28)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap(),
+    };
+    let rows = vec![
+        row("day", 100, 5, 11),
+        row("night", 50, 3, 11),
+        row("day", 160, 5, 12),
+        row("night", 70, 3, 12),
+    ];
+    commands.import_readings(rows, true).await.unwrap();
+    assert_eq!(commands.list_metrics(meter.id).await.unwrap().len(), 2);
+    (router(commands.clone(), TOKEN.to_string()), commands)
+}
+
+async fn send(router: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+async fn get(router: &Router, uri: &str, token: Option<&str>) -> (StatusCode, Value) {
+    let request = Request::get(uri);
+    let request = match token {
+        Some(token) => request.header(AUTHORIZATION, std::format!("Bearer {}", token)),
+        None => request,
+    };
+    send(router, request.body(Body::empty()).unwrap()).await
+}
+
+async fn post(router: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::post(uri)
+        .header(AUTHORIZATION, std::format!("Bearer {}", TOKEN))
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    send(router, request).await
+}
+
+#[tokio::test]
+async fn test_api_authorization() {
+    let (router, _) = test_router().await;
+    for token in [None, Some("wrong")] {
+        let (status, body) = get(&router, "/meters", token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body["error"].is_string());
+    }
+    assert_eq!(get(&router, "/meters", Some(TOKEN)).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_meters() {
+    let (router, commands) = test_router().await;
+    let meter = commands.list_meters().await.unwrap().remove(0);
+
+    let (status, meters) = get(&router, "/meters", Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        meters,
+        json!([{ "id": meter.id, "name": "power", "archived": false }])
+    );
+
+    let (status, found) = get(&router, &std::format!("/meters/{}", meter.id), Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(found["name"], "power");
+    assert_eq!(found["metrics"][0]["name"], "day");
+    assert_eq!(found["metrics"][1]["rate"], 3);
+
+    let (status, body) = get(&router, "/meters/100", Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].is_string());
+
+    // archived meters are only found by id
+    commands.archive_meter(meter.id).await.unwrap();
+    assert_eq!(get(&router, "/meters", Some(TOKEN)).await.1, json!([]));
+    let (_, found) = get(&router, &std::format!("/meters/{}", meter.id), Some(TOKEN)).await;
+    assert_eq!(found["archived"], true);
+}
+
+#[tokio::test]
+async fn test_api_readings() {
+    let (router, commands) = test_router().await;
+    let meter = commands.list_meters().await.unwrap().remove(0);
+    let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+    let uri = std::format!("/metrics/{}/readings", metric.id);
+
+    let (status, page) = get(&router, &uri, Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["total"], 2);
+    assert_eq!(page["items"][0]["value"], 160);
+    assert_eq!(page["items"][0]["added"], "2023-12-28 00:00:00");
+
+    let (_, page) = get(&router, &(uri.clone() + "?offset=1&limit=1"), Some(TOKEN)).await;
+    assert_eq!(page["offset"], 1);
+    assert_eq!(page["items"].as_array().unwrap().len(), 1);
+    assert_eq!(page["items"][0]["value"], 100);
+
+    let (status, _) = get(&router, &(uri + "?limit=x"), Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = get(&router, "/metrics/100/readings", Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_bill() {
+    let (router, commands) = test_router().await;
+    let month = NaiveDate::from_ymd_opt(2023, 12, 1).unwrap();
+    let report = commands.monthly_report(month).await.unwrap();
+
+    let (status, bill) = get(&router, "/bill?month=2023-12", Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(bill["month"], "2023-12");
+    assert_eq!(bill["total"], report.total);
+    assert_eq!(bill["lines"].as_array().unwrap().len(), report.lines.len());
+
+    let (status, _) = get(&router, "/bill?month=december", Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_api_submit_reading() {
+    let (router, commands) = test_router().await;
+    let meter = commands.list_meters().await.unwrap().remove(0);
+    let metric = commands.list_metrics(meter.id).await.unwrap().remove(0);
+    let uri = std::format!("/metrics/{}/readings", metric.id);
+
+    let (status, body) = post(&router, &uri, json!({ "value": 170 })).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["value"], 170);
+    assert_eq!(
+        get(&router, &uri, Some(TOKEN)).await.1["items"][0]["value"],
+        170
+    );
+
+    for value in [json!({}), json!({ "value": -1 }), json!({ "value": "x" })] {
+        assert_eq!(post(&router, &uri, value).await.0, StatusCode::BAD_REQUEST);
+    }
+    let (status, _) = post(&router, "/metrics/100/readings", json!({ "value": 1 })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // a smaller value of a counter with known digits must be confirmed
+    commands.set_digits(metric.id, Some(3)).await.unwrap();
+    let (status, _) = post(&router, &uri, json!({ "value": 1000 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = post(&router, &uri, json!({ "value": 5 })).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body["error"].is_string());
+    let rollover = json!({ "value": 5, "rollover": true });
+    assert_eq!(post(&router, &uri, rollover).await.0, StatusCode::CREATED);
+    assert_eq!(get(&router, &uri, Some(TOKEN)).await.1["total"], 4);
+
+    // the readings are recorded as made through the API, not by the bot
+    let records = commands.list_audit_page(0, 10).await.unwrap().items;
+    assert_eq!(records[0].entity, "metric_value");
+    assert_eq!(records[0].user_id, Some(0));
+
+    // archived meters take no new readings
+    commands.archive_meter(meter.id).await.unwrap();
+    let (status, body) = post(&router, &uri, json!({ "value": 6 })).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body["error"].is_string());
+    assert_eq!(get(&router, &uri, Some(TOKEN)).await.1["total"], 4);
+}